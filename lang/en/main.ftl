@@ -1 +1,2 @@
 greet = Hello, {$name}
+level-up = GG {$mention}, you just reached level {$level}!
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE guilds
+  DROP COLUMN min_xp,
+  DROP COLUMN max_xp,
+  DROP COLUMN xp_timeout_secs
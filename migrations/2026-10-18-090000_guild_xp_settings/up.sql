@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE guilds
+  ADD COLUMN min_xp INTEGER DEFAULT 15 NOT NULL,
+  ADD COLUMN max_xp INTEGER DEFAULT 25 NOT NULL,
+  ADD COLUMN xp_timeout_secs INTEGER DEFAULT 60 NOT NULL
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE level_rewards (
+  id SERIAL PRIMARY KEY,
+  guild_id BIGINT NOT NULL,
+  role_id BIGINT NOT NULL,
+  level INTEGER NOT NULL,
+  UNIQUE (guild_id, role_id)
+)
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE guilds
+  ADD COLUMN levelup_message TEXT
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE guilds
+  DROP COLUMN levelup_mode,
+  DROP COLUMN levelup_channel
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE guilds
+  ADD COLUMN levelup_mode VARCHAR(16) DEFAULT 'current' NOT NULL,
+  ADD COLUMN levelup_channel BIGINT
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE guilds
+  DROP COLUMN xp_ignored_channels,
+  DROP COLUMN xp_ignored_roles,
+  DROP COLUMN xp_allowed_channels,
+  DROP COLUMN xp_whitelist_only
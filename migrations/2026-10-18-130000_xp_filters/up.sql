@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE guilds
+  ADD COLUMN xp_ignored_channels BIGINT[] DEFAULT '{}' NOT NULL,
+  ADD COLUMN xp_ignored_roles BIGINT[] DEFAULT '{}' NOT NULL,
+  ADD COLUMN xp_allowed_channels BIGINT[] DEFAULT '{}' NOT NULL,
+  ADD COLUMN xp_whitelist_only BOOLEAN DEFAULT 'f' NOT NULL
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE guilds
+  DROP COLUMN xp_multiplier_stack;
+
+DROP TABLE xp_multipliers
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE xp_multipliers (
+  id SERIAL PRIMARY KEY,
+  guild_id BIGINT NOT NULL,
+  target_id BIGINT NOT NULL,
+  kind VARCHAR(16) NOT NULL,
+  multiplier REAL NOT NULL,
+  UNIQUE (guild_id, target_id)
+);
+
+ALTER TABLE guilds
+  ADD COLUMN xp_multiplier_stack VARCHAR(16) DEFAULT 'multiply' NOT NULL
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE guilds
+  DROP COLUMN voice_xp_enabled,
+  DROP COLUMN voice_xp_per_minute,
+  DROP COLUMN voice_exclude_muted,
+  DROP COLUMN voice_require_others,
+  DROP COLUMN voice_exclude_afk
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE guilds
+  ADD COLUMN voice_xp_enabled BOOLEAN DEFAULT 'f' NOT NULL,
+  ADD COLUMN voice_xp_per_minute INTEGER DEFAULT 10 NOT NULL,
+  ADD COLUMN voice_exclude_muted BOOLEAN DEFAULT 't' NOT NULL,
+  ADD COLUMN voice_require_others BOOLEAN DEFAULT 't' NOT NULL,
+  ADD COLUMN voice_exclude_afk BOOLEAN DEFAULT 't' NOT NULL
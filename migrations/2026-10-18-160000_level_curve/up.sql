@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE guilds
+  ADD COLUMN level_curve VARCHAR DEFAULT 'quadratic' NOT NULL
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+CREATE INDEX users_leaderboard_idx ON users (guild_id, xp DESC, user_id DESC)
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE user_settings (
+  user_id BIGINT PRIMARY KEY,
+  hide_guilds BOOLEAN DEFAULT 'f' NOT NULL
+)
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE xp_daily;
+DROP TABLE xp_events
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+CREATE TABLE xp_events (
+  id BIGSERIAL PRIMARY KEY,
+  guild_id BIGINT NOT NULL,
+  user_id BIGINT NOT NULL,
+  xp INTEGER NOT NULL,
+  created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL
+);
+
+CREATE INDEX xp_events_guild_idx ON xp_events (guild_id, created_at);
+CREATE INDEX xp_events_user_idx ON xp_events (guild_id, user_id, created_at);
+
+-- one row per member per day (UTC) so windowed leaderboards don't have to
+-- scan the event log
+CREATE TABLE xp_daily (
+  guild_id BIGINT NOT NULL,
+  day DATE NOT NULL,
+  user_id BIGINT NOT NULL,
+  xp BIGINT DEFAULT 0 NOT NULL,
+  PRIMARY KEY (guild_id, day, user_id)
+)
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER xp_events_append_only ON xp_events;
+DROP FUNCTION xp_events_append_only();
+
+ALTER TABLE xp_events
+  DROP COLUMN actor_id,
+  DROP COLUMN source
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+ALTER TABLE xp_events
+  ADD COLUMN source VARCHAR(16) DEFAULT 'message' NOT NULL,
+  ADD COLUMN actor_id BIGINT;
+
+-- the ledger is append-only, so changes are recorded as new rows instead
+CREATE FUNCTION xp_events_append_only() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION 'xp_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER xp_events_append_only
+  BEFORE UPDATE ON xp_events
+  FOR EACH ROW EXECUTE PROCEDURE xp_events_append_only()
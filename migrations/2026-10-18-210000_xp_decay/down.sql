@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_last_xp_idx;
+
+ALTER TABLE users
+  DROP COLUMN last_xp_at;
+
+ALTER TABLE guilds
+  DROP COLUMN decay_enabled,
+  DROP COLUMN decay_grace_days,
+  DROP COLUMN decay_mode,
+  DROP COLUMN decay_amount,
+  DROP COLUMN decay_min_level,
+  DROP COLUMN decay_last_run
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+ALTER TABLE guilds
+  ADD COLUMN decay_enabled BOOLEAN DEFAULT 'f' NOT NULL,
+  ADD COLUMN decay_grace_days INTEGER DEFAULT 14 NOT NULL,
+  ADD COLUMN decay_mode VARCHAR(16) DEFAULT 'percent' NOT NULL,
+  ADD COLUMN decay_amount INTEGER DEFAULT 1 NOT NULL,
+  ADD COLUMN decay_min_level INTEGER DEFAULT 0 NOT NULL,
+  ADD COLUMN decay_last_run DATE;
+
+-- existing members start their grace period now rather than decaying straight
+-- away
+ALTER TABLE users
+  ADD COLUMN last_xp_at TIMESTAMPTZ DEFAULT NOW() NOT NULL;
+
+CREATE INDEX users_last_xp_idx ON users (guild_id, last_xp_at)
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+CREATE TABLE seasons (
+  id SERIAL PRIMARY KEY,
+  guild_id BIGINT NOT NULL,
+  number INTEGER NOT NULL,
+  -- NULL for a guild's first season
+  started_at TIMESTAMPTZ,
+  ended_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+  UNIQUE (guild_id, number)
+);
+
+CREATE TABLE season_standings (
+  season_id INTEGER NOT NULL REFERENCES seasons (id) ON DELETE CASCADE,
+  position INTEGER NOT NULL,
+  user_id BIGINT NOT NULL,
+  xp INTEGER NOT NULL,
+  PRIMARY KEY (season_id, position)
+)
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX guilds_left_at_idx;
+
+ALTER TABLE users
+  DROP COLUMN left_at;
+
+ALTER TABLE guilds
+  DROP COLUMN departed_mode,
+  DROP COLUMN left_at
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+ALTER TABLE guilds
+  ADD COLUMN departed_mode VARCHAR(16) DEFAULT 'keep' NOT NULL,
+  ADD COLUMN left_at TIMESTAMPTZ;
+
+-- set for members who left a guild that hides them from the leaderboard
+ALTER TABLE users
+  ADD COLUMN left_at TIMESTAMPTZ;
+
+CREATE INDEX guilds_left_at_idx ON guilds (left_at) WHERE left_at IS NOT NULL
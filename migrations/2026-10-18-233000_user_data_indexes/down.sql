@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX season_standings_user_id_idx;
+
+DROP INDEX xp_daily_user_id_idx;
+
+DROP INDEX xp_events_user_id_idx
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- for exporting and erasing a user's data in every guild at once
+CREATE INDEX xp_events_user_id_idx ON xp_events (user_id, id);
+
+CREATE INDEX xp_daily_user_id_idx ON xp_daily (user_id);
+
+CREATE INDEX season_standings_user_id_idx ON season_standings (user_id)
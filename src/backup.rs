@@ -0,0 +1,341 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{
+        guild::{AnnounceMode, DecayMode, DepartedMode, Guild},
+        multiplier::{MultiplierStack, MultiplierTarget, XpMultiplier},
+        reward::LevelReward,
+        season::{Season, SeasonStanding},
+        user::User,
+    },
+    util::curve::LevelCurve,
+    DECAY_GRACE_LIMIT_DAYS,
+    DECAY_XP_LIMIT,
+    MESSAGE_XP_LIMIT,
+    MULTIPLIER_LIMIT,
+    VOICE_XP_LIMIT,
+    XP_TIMEOUT_LIMIT_SECS,
+};
+
+/// The version of the backup format. It goes up whenever a table is added to
+/// backups or a backed up table changes, and a backup can only be restored by
+/// a bot that reads the same version
+pub const BACKUP_VERSION: u32 = 2;
+
+/// Everything stored for a guild, with each table's rows as they are in the
+/// database. Row IDs are given new values when a backup is restored. The XP
+/// ledger and daily totals aren't included, as they'd make large guilds'
+/// backups too big to upload
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuildBackup {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub guild: Guild,
+    pub users: Vec<User>,
+    pub level_rewards: Vec<LevelReward>,
+    pub xp_multipliers: Vec<XpMultiplier>,
+    pub seasons: Vec<Season>,
+    /// Matched to their season by its row ID in `seasons`
+    pub season_standings: Vec<SeasonStanding>,
+}
+
+impl GuildBackup {
+    /// Read a backup, checking it's in the version of the format this bot
+    /// reads before anything else
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+
+        let Version { version } = serde_json::from_slice(data)
+            .map_err(|_| "That isn't a backup".to_string())?;
+
+        if version != BACKUP_VERSION {
+            return Err(format!(
+                "That backup is version {}, but only version {} backups can \
+                 be restored",
+                version, BACKUP_VERSION
+            ));
+        }
+
+        let backup: Self = serde_json::from_slice(data)
+            .map_err(|e| format!("The backup is damaged: {}", e))?;
+
+        backup.validate()?;
+
+        Ok(backup)
+    }
+
+    /// Drop everything that points at the backed up guild's own channels and
+    /// roles, which don't exist in any other guild: the level-up channel, the
+    /// XP filters, level rewards and multipliers
+    pub fn drop_guild_ids(&mut self) {
+        let guild = &mut self.guild;
+
+        guild.levelup_channel = None;
+        if guild.announce_mode() == AnnounceMode::Channel {
+            guild.levelup_mode = AnnounceMode::Current.as_str().to_string();
+        }
+
+        guild.xp_ignored_channels.clear();
+        guild.xp_ignored_roles.clear();
+        guild.xp_allowed_channels.clear();
+        // an empty allow list would stop anyone earning XP
+        guild.xp_whitelist_only = false;
+
+        self.level_rewards.clear();
+        self.xp_multipliers.clear();
+    }
+
+    /// Check the backup's settings are within the same limits the settings
+    /// commands have, since a backup can be edited before it's restored
+    fn validate(&self) -> Result<(), String> {
+        let guild = &self.guild;
+
+        in_range("minimum XP per message", guild.min_xp, 0, MESSAGE_XP_LIMIT)?;
+        in_range(
+            "maximum XP per message",
+            guild.max_xp,
+            guild.min_xp,
+            MESSAGE_XP_LIMIT,
+        )?;
+        in_range(
+            "XP cooldown",
+            guild.xp_timeout_secs,
+            0,
+            XP_TIMEOUT_LIMIT_SECS,
+        )?;
+        in_range(
+            "voice XP per minute",
+            guild.voice_xp_per_minute,
+            0,
+            VOICE_XP_LIMIT,
+        )?;
+        in_range(
+            "decay grace period",
+            guild.decay_grace_days,
+            0,
+            DECAY_GRACE_LIMIT_DAYS,
+        )?;
+
+        let decay_limit =
+            match known::<DecayMode>("decay mode", &guild.decay_mode)? {
+                DecayMode::Percent => 100,
+                DecayMode::Fixed => DECAY_XP_LIMIT,
+            };
+        in_range("decay amount", guild.decay_amount, 1, decay_limit)?;
+
+        known::<AnnounceMode>("level-up mode", &guild.levelup_mode)?;
+        known::<MultiplierStack>(
+            "multiplier stacking",
+            &guild.xp_multiplier_stack,
+        )?;
+        known::<DepartedMode>("departed member mode", &guild.departed_mode)?;
+
+        let curve = guild.level_curve.parse::<LevelCurve>().map_err(|e| {
+            format!("The backup's leveling curve is invalid: {}", e)
+        })?;
+        in_range(
+            "decay minimum level",
+            guild.decay_min_level,
+            0,
+            curve.max_level(),
+        )?;
+
+        for m in &self.xp_multipliers {
+            known::<MultiplierTarget>("multiplier kind", &m.kind)?;
+
+            if !(0.0..=MULTIPLIER_LIMIT).contains(&m.multiplier) {
+                return Err(format!(
+                    "The backup has a {}x multiplier, but multipliers have to \
+                     be between 0 and {}",
+                    m.multiplier, MULTIPLIER_LIMIT
+                ));
+            }
+        }
+
+        if let Some(r) = self.level_rewards.iter().find(|r| r.level < 1) {
+            return Err(format!(
+                "The backup has a level reward at level {}",
+                r.level
+            ));
+        }
+
+        if let Some(u) = self.users.iter().find(|u| u.xp < 0) {
+            return Err(format!(
+                "The backup gives user {} negative XP",
+                u.user_id
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Check one of a backup's numbers is within `min..=max`
+fn in_range(what: &str, value: i32, min: i32, max: i32) -> Result<(), String> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "The backup's {} is {}, but it has to be between {} and {}",
+            what, value, min, max
+        ))
+    }
+}
+
+/// Check one of a backup's settings is a value the bot knows
+fn known<T: FromStr>(what: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("The backup's {} `{}` isn't valid", what, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::util::curve::MAX_LEVEL;
+
+    /// A backup of a guild on the default settings, with one member
+    fn backup() -> Value {
+        json!({
+            "version": BACKUP_VERSION,
+            "created_at": "2026-10-18T00:00:00Z",
+            "guild": {
+                "id": 1,
+                "guild_id": 1,
+                "prefix": "~",
+                "min_xp": 15,
+                "max_xp": 25,
+                "xp_timeout_secs": 60,
+                "levelup_message": null,
+                "levelup_mode": "current",
+                "levelup_channel": null,
+                "xp_ignored_channels": [],
+                "xp_ignored_roles": [],
+                "xp_allowed_channels": [],
+                "xp_whitelist_only": false,
+                "xp_multiplier_stack": "multiply",
+                "voice_xp_enabled": false,
+                "voice_xp_per_minute": 10,
+                "voice_exclude_muted": true,
+                "voice_require_others": true,
+                "voice_exclude_afk": true,
+                "level_curve": "quadratic",
+                "decay_enabled": false,
+                "decay_grace_days": 14,
+                "decay_mode": "percent",
+                "decay_amount": 1,
+                "decay_min_level": 0,
+                "decay_last_run": null,
+                "departed_mode": "keep",
+                "left_at": null,
+            },
+            "users": [{
+                "id": 1,
+                "user_id": 2,
+                "guild_id": 1,
+                "xp": 100,
+                "blocked": false,
+                "last_xp_at": "2026-10-18T00:00:00Z",
+                "left_at": null,
+            }],
+            "level_rewards": [],
+            "xp_multipliers": [{
+                "id": 1,
+                "guild_id": 1,
+                "target_id": 3,
+                "kind": "role",
+                "multiplier": 2.0,
+            }],
+            "seasons": [],
+            "season_standings": [],
+        })
+    }
+
+    fn parse(backup: &Value) -> Result<GuildBackup, String> {
+        GuildBackup::parse(&serde_json::to_vec(backup).unwrap())
+    }
+
+    #[test]
+    fn valid_backup_parses() {
+        assert!(parse(&backup()).is_ok());
+    }
+
+    #[test]
+    fn settings_outside_limits_are_rejected() {
+        let invalid = [
+            ("/guild/min_xp", json!(30)),
+            ("/guild/max_xp", json!(MESSAGE_XP_LIMIT + 1)),
+            ("/guild/xp_timeout_secs", json!(-1)),
+            ("/guild/voice_xp_per_minute", json!(VOICE_XP_LIMIT + 1)),
+            ("/guild/decay_grace_days", json!(DECAY_GRACE_LIMIT_DAYS + 1)),
+            ("/guild/decay_amount", json!(101)),
+            ("/guild/decay_mode", json!("sometimes")),
+            ("/guild/levelup_mode", json!("loudly")),
+            ("/guild/level_curve", json!("exponential 0 0")),
+            ("/guild/decay_min_level", json!(MAX_LEVEL + 1)),
+            (
+                "/xp_multipliers/0/multiplier",
+                json!(MULTIPLIER_LIMIT * 2.0),
+            ),
+            ("/xp_multipliers/0/kind", json!("emoji")),
+            ("/users/0/xp", json!(-5)),
+        ];
+
+        for (path, value) in &invalid {
+            let mut backup = backup();
+            *backup.pointer_mut(path).unwrap() = value.clone();
+
+            assert!(
+                parse(&backup).is_err(),
+                "{} = {} was accepted",
+                path,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn decay_floor_must_be_on_the_curve() {
+        let mut backup = backup();
+        backup["guild"]["level_curve"] = json!("table 10 20 30");
+        backup["guild"]["decay_min_level"] = json!(3);
+        assert!(parse(&backup).is_ok());
+
+        backup["guild"]["decay_min_level"] = json!(4);
+        assert!(parse(&backup).is_err());
+    }
+
+    #[test]
+    fn guild_ids_are_dropped() {
+        let mut backup = backup();
+        backup["guild"]["levelup_mode"] = json!("channel");
+        backup["guild"]["levelup_channel"] = json!(4);
+        backup["guild"]["xp_allowed_channels"] = json!([5]);
+        backup["guild"]["xp_whitelist_only"] = json!(true);
+        backup["level_rewards"] = json!([{
+            "id": 1,
+            "guild_id": 1,
+            "role_id": 6,
+            "level": 5,
+        }]);
+
+        let mut backup = parse(&backup).unwrap();
+        backup.drop_guild_ids();
+
+        assert_eq!(backup.guild.announce_mode(), AnnounceMode::Current);
+        assert_eq!(backup.guild.levelup_channel, None);
+        assert!(backup.guild.xp_allowed_channels.is_empty());
+        assert!(!backup.guild.xp_whitelist_only);
+        assert!(backup.level_rewards.is_empty());
+        assert!(backup.xp_multipliers.is_empty());
+        assert_eq!(backup.users.len(), 1);
+    }
+}
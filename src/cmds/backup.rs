@@ -0,0 +1,157 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    http::AttachmentType,
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    backup::GuildBackup,
+    cmds::import::attachment_data,
+    db::postgres::Database,
+    export::EXPORT_SIZE_LIMIT,
+    util::format::thousands,
+};
+
+#[command("backup")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Back up everything the bot stores for this server: its \
+                 settings, everyone's XP, level rewards, multipliers and \
+                 seasons. Restore it here or in another server with `restore`"]
+pub async fn backup_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let backup = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        db.backup_guild(guild_id)?
+    };
+
+    let data = serde_json::to_vec(&backup)?;
+
+    if data.len() > EXPORT_SIZE_LIMIT {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "The backup is too big to upload (over {} MB)",
+                    EXPORT_SIZE_LIMIT / 1024 / 1024
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let file = AttachmentType::Bytes {
+        data: data.into(),
+        filename: format!(
+            "backup-{}-{}.json",
+            guild_id,
+            backup.created_at.format("%Y-%m-%d")
+        ),
+    };
+
+    msg.channel_id
+        .send_files(&ctx.http, vec![file], |m| {
+            m.content(format!(
+                "Backed up {}. Keep the file somewhere safe, and attach it to \
+                 `restore` to put everything back",
+                describe_backup(&backup)
+            ))
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("restore")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Replace everything the bot stores for this server with a \
+                 backup. Attach the backup file to the message. Nothing is \
+                 changed until you run it again with `confirm`"]
+#[usage = "[confirm]"]
+#[example = "confirm"]
+#[max_args(1)]
+pub async fn restore_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let confirmed = match args.rest().trim() {
+        "" => false,
+        arg if arg.eq_ignore_ascii_case("confirm") => true,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Expected `confirm` or nothing")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let data = match attachment_data(ctx, msg, "backup").await? {
+        Some(d) => d,
+        None => return Ok(()),
+    };
+
+    let backup = match GuildBackup::parse(&data) {
+        Ok(b) => b,
+        Err(e) => {
+            msg.channel_id.say(&ctx.http, e).await?;
+            return Ok(());
+        },
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let mut m = format!(
+        "This backup from {} has {}",
+        backup.created_at.format("%Y-%m-%d %H:%M UTC"),
+        describe_backup(&backup)
+    );
+
+    if backup.guild.guild_id != guild_id.0 as i64 {
+        m.push_str(
+            ". It was made in another server, so its level-up channel, XP \
+             filters, level rewards and multipliers will be left out",
+        );
+    }
+
+    if !confirmed {
+        m.push_str(
+            "\nRestoring it replaces all of this server's settings, XP, \
+             level rewards, multipliers and seasons, and can't be undone. \
+             Run `restore confirm` with the backup attached to go ahead",
+        );
+    } else {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        db.restore_guild(guild_id, backup, msg.author.id)?;
+
+        m.push_str("\nThe backup has been restored");
+    }
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+/// What's in a backup, like "12 members, 3 level rewards, ..."
+fn describe_backup(backup: &GuildBackup) -> String {
+    format!(
+        "{} members, {} level rewards, {} multipliers and {} seasons",
+        thousands(backup.users.len() as i64),
+        backup.level_rewards.len(),
+        backup.xp_multipliers.len(),
+        backup.seasons.len()
+    )
+}
@@ -0,0 +1,115 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    http::AttachmentType,
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    export::{
+        ExportFormat,
+        ExportRow,
+        ExportWriter,
+        EXPORT_BATCH_SIZE,
+        EXPORT_SIZE_LIMIT,
+    },
+    models::user::RankedUser,
+    util::format::thousands,
+};
+
+#[command("export")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Export everyone's XP, level and rank as a CSV (the default) \
+                 or JSON file. Blocked and hidden members are included, \
+                 without a rank"]
+#[usage = "[csv|json]"]
+#[example = "json"]
+#[max_args(1)]
+pub async fn export_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let format = match args.rest().trim() {
+        "" => ExportFormat::Csv,
+        arg => match arg.parse() {
+            Ok(f) => f,
+            Err(_) => {
+                msg.channel_id
+                    .say(&ctx.http, "Expected `csv` or `json`")
+                    .await?;
+                return Ok(());
+            },
+        },
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+    let (curve, mut users) = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        (
+            db.get_guild(guild_id)?.curve(),
+            db.read_guild_users(guild_id)?,
+        )
+    };
+    let mut writer = ExportWriter::new(format);
+
+    loop {
+        // the cursor has its own connection, so XP can still be earned
+        // between batches without changing what's exported
+        let batch = users.next_batch(EXPORT_BATCH_SIZE)?;
+
+        for RankedUser { user: u, rank } in &batch {
+            let username = ctx.cache.user(u.user_id as u64).await;
+
+            writer.write(&ExportRow {
+                user_id: u.user_id.to_string(),
+                username: username.map(|user| user.tag()),
+                xp: u.xp,
+                level: curve.level_for_xp(u.xp),
+                rank: *rank,
+                blocked: u.blocked,
+                hidden: u.left_at.is_some(),
+            });
+        }
+
+        if writer.size() > EXPORT_SIZE_LIMIT {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "The export is too big to upload (over {} MB)",
+                        EXPORT_SIZE_LIMIT / 1024 / 1024
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        if (batch.len() as i64) < EXPORT_BATCH_SIZE {
+            break;
+        }
+    }
+
+    drop(users);
+
+    let rows = writer.rows();
+    let file = AttachmentType::Bytes {
+        data: writer.finish().into(),
+        filename: format!("leaderboard-{}.{}", guild_id, format),
+    };
+
+    msg.channel_id
+        .send_files(&ctx.http, vec![file], |m| {
+            m.content(format!("Exported {} members", thousands(rows as i64)))
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    models::guild::Guild as GuildSettings,
+    util::parse::{parse_channel, parse_role},
+};
+
+#[command("noxp")]
+#[aliases("xp_filters")]
+#[sub_commands(
+    noxp_channel_cmd,
+    noxp_role_cmd,
+    noxp_allow_cmd,
+    noxp_whitelist_cmd
+)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "List the channels and roles that don't earn XP"]
+pub async fn noxp_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guild = db.get_guild(msg.guild_id.unwrap())?;
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse())
+                .content(format_filters(&guild))
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("channel")]
+#[aliases("category")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Toggle whether a channel, or every channel in a category, \
+                 earns XP"]
+#[usage = "<channel|category>"]
+#[example = "#bot-spam"]
+#[min_args(1)]
+pub async fn noxp_channel_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let channel_id = match parse_channel(ctx, guild_id, args.rest()).await {
+        Some(c) => c,
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown channel").await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let mut filters = db.get_guild(guild_id)?.xp_filters();
+
+    let m = if toggle(&mut filters.xp_ignored_channels, channel_id.0) {
+        format!("<#{}> will no longer earn XP", channel_id)
+    } else {
+        format!("<#{}> will earn XP again", channel_id)
+    };
+
+    db.set_guild_xp_filters(guild_id, &filters)?;
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+#[command("role")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Toggle whether members with a role earn XP"]
+#[usage = "<role>"]
+#[example = "@Muted"]
+#[min_args(1)]
+pub async fn noxp_role_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild = match msg.guild(&ctx.cache).await {
+        Some(g) => g,
+        None => return Ok(()),
+    };
+
+    let role_id = match parse_role(&guild, args.rest()) {
+        Some(r) => r,
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown role").await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let mut filters = db.get_guild(guild.id)?.xp_filters();
+
+    let m = if toggle(&mut filters.xp_ignored_roles, role_id.0) {
+        format!("Members with <@&{}> will no longer earn XP", role_id)
+    } else {
+        format!("Members with <@&{}> will earn XP again", role_id)
+    };
+
+    db.set_guild_xp_filters(guild.id, &filters)?;
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("allow")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Toggle whether a channel or category is on the whitelist. \
+                 The whitelist is only used when whitelist mode is on"]
+#[usage = "<channel|category>"]
+#[example = "#general"]
+#[min_args(1)]
+pub async fn noxp_allow_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let channel_id = match parse_channel(ctx, guild_id, args.rest()).await {
+        Some(c) => c,
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown channel").await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let mut filters = db.get_guild(guild_id)?.xp_filters();
+
+    let mut m = if toggle(&mut filters.xp_allowed_channels, channel_id.0) {
+        format!("<#{}> was added to the whitelist", channel_id)
+    } else {
+        format!("<#{}> was removed from the whitelist", channel_id)
+    };
+
+    if !filters.xp_whitelist_only {
+        m.push_str(
+            "\nWhitelist mode is off, so this won't do anything until it is \
+             turned on",
+        );
+    }
+
+    db.set_guild_xp_filters(guild_id, &filters)?;
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+#[command("whitelist")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Turn whitelist mode on or off. While it is on, only \
+                 whitelisted channels earn XP"]
+#[usage = "<on|off>"]
+#[example = "on"]
+#[num_args(1)]
+pub async fn noxp_whitelist_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let enabled = match args.single::<String>()?.to_lowercase().as_str() {
+        "on" | "true" | "enable" => true,
+        "off" | "false" | "disable" => false,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Expected `on` or `off`")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guild_id = msg.guild_id.unwrap();
+    let mut filters = db.get_guild(guild_id)?.xp_filters();
+    filters.xp_whitelist_only = enabled;
+
+    let saved = db.set_guild_xp_filters(guild_id, &filters)?;
+
+    let m = match (enabled, saved.xp_allowed_channels.is_empty()) {
+        (true, true) => {
+            "Whitelist mode is on. No channels are whitelisted yet, so nobody \
+             will earn XP until you add one"
+        },
+        (true, false) => "Whitelist mode is on",
+        (false, _) => "Whitelist mode is off",
+    };
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+/// Add `id` to `list` if it isn't there, otherwise remove it. Returns whether
+/// it was added
+fn toggle(list: &mut Vec<i64>, id: u64) -> bool {
+    let id = id as i64;
+
+    if let Some(i) = list.iter().position(|x| *x == id) {
+        list.remove(i);
+        false
+    } else {
+        list.push(id);
+        true
+    }
+}
+
+fn format_filters(guild: &GuildSettings) -> String {
+    let channels = |list: &[i64]| match list.len() {
+        0 => "none".to_string(),
+        _ => list
+            .iter()
+            .map(|c| format!("<#{}>", c))
+            .collect::<Vec<String>>()
+            .join(", "),
+    };
+
+    let roles = match guild.xp_ignored_roles.len() {
+        0 => "none".to_string(),
+        _ => guild
+            .xp_ignored_roles
+            .iter()
+            .map(|r| format!("<@&{}>", r))
+            .collect::<Vec<String>>()
+            .join(", "),
+    };
+
+    format!(
+        "**XP filters**\n\
+         Ignored channels: {}\n\
+         Ignored roles: {}\n\
+         Whitelist mode: {}\n\
+         Whitelisted channels: {}",
+        channels(&guild.xp_ignored_channels),
+        roles,
+        if guild.xp_whitelist_only { "on" } else { "off" },
+        channels(&guild.xp_allowed_channels),
+    )
+}
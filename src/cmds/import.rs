@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    import::{
+        map_xp,
+        parse_leaderboard,
+        ImportFormat,
+        ImportMode,
+        IMPORT_SIZE_LIMIT,
+    },
+    util::format::thousands,
+};
+
+#[command("import")]
+#[sub_commands(import_file_cmd)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Import the leaderboard from MEE6, Tatsu, Amari or a CSV \
+                 file. Attach the exported leaderboard to the message, and \
+                 choose what happens to members who already have XP: \
+                 `overwrite` it, keep the `max` of the two (the default) or \
+                 `add` them together. Members keep the level they had, on \
+                 this server's leveling curve. Nothing is changed until you \
+                 run it again with `confirm`"]
+#[usage = "[mee6|tatsu|amari|csv] [overwrite|max|add] [confirm]"]
+#[example = "mee6 max confirm"]
+#[max_args(3)]
+pub async fn import_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let options = match ImportOptions::parse(args.raw()) {
+        Ok(o) => o,
+        Err(e) => {
+            msg.channel_id.say(&ctx.http, e).await?;
+            return Ok(());
+        },
+    };
+
+    let data = match attachment_data(ctx, msg, "leaderboard").await? {
+        Some(d) => d,
+        None => return Ok(()),
+    };
+
+    import_leaderboard(ctx, msg, &data, options).await
+}
+
+#[command("file")]
+#[owners_only]
+#[description = "Import a leaderboard file from the bot's machine"]
+#[usage = "<path> [mee6|tatsu|amari|csv] [overwrite|max|add] [confirm]"]
+#[example = "exports/mee6.json mee6 max confirm"]
+#[min_args(1)]
+#[max_args(4)]
+pub async fn import_file_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let path = args.single_quoted::<String>()?;
+
+    let options = match ImportOptions::parse(args.rest().split_whitespace()) {
+        Ok(o) => o,
+        Err(e) => {
+            msg.channel_id.say(&ctx.http, e).await?;
+            return Ok(());
+        },
+    };
+
+    let data =
+        match tokio::task::spawn_blocking(move || std::fs::read(path)).await? {
+            Ok(d) => d,
+            Err(e) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Couldn't read the file: {}", e))
+                    .await?;
+                return Ok(());
+            },
+        };
+
+    import_leaderboard(ctx, msg, &data, options).await
+}
+
+/// Download the file attached to a message, replying if there isn't one or
+/// it's too big. `what` is the kind of file the command expects
+pub async fn attachment_data(
+    ctx: &Context,
+    msg: &Message,
+    what: &str,
+) -> Result<Option<Vec<u8>>, SerenityError> {
+    let attachment = match msg.attachments.first() {
+        Some(a) if a.size > IMPORT_SIZE_LIMIT => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "The {} file can't be bigger than {} MB",
+                        what,
+                        IMPORT_SIZE_LIMIT / 1024 / 1024
+                    ),
+                )
+                .await?;
+            return Ok(None);
+        },
+        Some(a) => a,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Attach the {} file", what))
+                .await?;
+            return Ok(None);
+        },
+    };
+
+    attachment.download().await.map(Some)
+}
+
+/// The choices given to the import commands
+#[derive(Debug, Clone, Copy)]
+struct ImportOptions {
+    format: Option<ImportFormat>,
+    mode: ImportMode,
+    confirmed: bool,
+}
+
+impl ImportOptions {
+    /// Read the options, which can be given in any order
+    fn parse<'a>(args: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let mut options = Self {
+            format: None,
+            mode: ImportMode::KeepMax,
+            confirmed: false,
+        };
+
+        for arg in args {
+            if let Ok(format) = arg.parse() {
+                options.format = Some(format);
+            } else if let Ok(mode) = arg.parse() {
+                options.mode = mode;
+            } else if arg.eq_ignore_ascii_case("confirm") {
+                options.confirmed = true;
+            } else {
+                return Err(format!(
+                    "Unknown option `{}`. Expected a format (`mee6`, \
+                     `tatsu`, `amari` or `csv`), a mode (`overwrite`, `max` \
+                     or `add`) or `confirm`",
+                    arg
+                ));
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Read a leaderboard, map everyone's XP onto this server's leveling curve,
+/// and either show what importing it would do or go ahead with it
+async fn import_leaderboard(
+    ctx: &Context,
+    msg: &Message,
+    data: &[u8],
+    options: ImportOptions,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let leaderboard = match parse_leaderboard(data, options.format) {
+        Ok(l) => l,
+        Err(e) => {
+            msg.channel_id.say(&ctx.http, e).await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let curve = db.get_guild(guild_id)?.curve();
+    let source = leaderboard.format.curve();
+    let existing = db
+        .get_guild_users(guild_id)?
+        .into_iter()
+        .map(|u| (u.user_id, u.xp))
+        .collect::<HashMap<i64, i32>>();
+
+    let (mut new, mut changed, mut unchanged) = (0, 0, 0);
+    let mut difference = 0i64;
+    let mut imported = Vec::with_capacity(leaderboard.entries.len());
+
+    for entry in &leaderboard.entries {
+        let xp = map_xp(entry, source.as_ref(), &curve);
+
+        let old = match existing.get(&(entry.user_id as i64)) {
+            Some(old) if options.mode.merge(*old, xp) == *old => {
+                unchanged += 1;
+                continue;
+            },
+            Some(old) => {
+                changed += 1;
+                *old
+            },
+            // nothing to import for someone who has no XP anywhere
+            None if xp == 0 => {
+                unchanged += 1;
+                continue;
+            },
+            None => {
+                new += 1;
+                0
+            },
+        };
+
+        difference += (options.mode.merge(old, xp) - old) as i64;
+        imported.push((UserId(entry.user_id), xp));
+    }
+
+    let mut m = format!(
+        "Read {} members from a {} leaderboard",
+        thousands(leaderboard.entries.len() as i64),
+        leaderboard.format
+    );
+
+    if leaderboard.skipped > 0 {
+        m.push_str(&format!(
+            " ({} rows were skipped or listed a member again)",
+            thousands(leaderboard.skipped as i64)
+        ));
+    }
+
+    m.push_str(&format!(
+        "\n{} new members, {} members whose XP changes ({}), {} unchanged",
+        thousands(new),
+        thousands(changed),
+        options.mode,
+        thousands(unchanged)
+    ));
+
+    if imported.is_empty() {
+        m.push_str("\nThere's nothing to import");
+    } else if options.confirmed {
+        let users = db.import_guild_users(
+            guild_id,
+            &imported,
+            options.mode,
+            msg.author.id,
+        )?;
+
+        m.push_str(&format!(
+            "\nImported {} members, a change of {} XP in total",
+            thousands(users.len() as i64),
+            thousands(difference)
+        ));
+    } else {
+        m.push_str(&format!(
+            "\nThat's a change of {} XP in total. Nothing has been changed \
+             yet: run the command again with `confirm` to go ahead",
+            thousands(difference)
+        ));
+    }
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    models::guild::AnnounceMode,
+    util::template::{render_level_up, validate_template, LevelUpVars},
+};
+
+#[command("levelup")]
+#[aliases("level_up")]
+#[sub_commands(
+    levelup_message_cmd,
+    levelup_preview_cmd,
+    levelup_mode_cmd,
+    levelup_channel_cmd
+)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "View how level-ups are announced in this server"]
+pub async fn levelup_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guild = db.get_guild(msg.guild_id.unwrap())?;
+
+    let mode = match (guild.announce_mode(), guild.levelup_channel) {
+        (AnnounceMode::Channel, Some(c)) => format!("<#{}>", c),
+        (mode, _) => format!("`{}`", mode),
+    };
+
+    let m = match guild.levelup_message {
+        Some(t) => format!(
+            "Announced in: {}\nLevel-up message:\n```\n{}\n```",
+            mode, t
+        ),
+        None => format!("Announced in: {}\nLevel-up message: *default*", mode),
+    };
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+#[command("message")]
+#[aliases("msg", "template")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Set the message sent when a member levels up. Use `reset` to \
+                 go back to the default.\n\nAvailable variables: `{$mention}`, \
+                 `{$username}`, `{$level}`, `{$xp}`, `{$xp_to_next}`, \
+                 `{$server}`"]
+#[usage = "<message|reset>"]
+#[example = "GG {$mention}, you're now level {$level}!"]
+#[min_args(1)]
+pub async fn levelup_message_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let template = args.rest().trim();
+
+    let new_template = if template.eq_ignore_ascii_case("reset") {
+        None
+    } else if let Err(e) = validate_template(template) {
+        msg.channel_id.say(&ctx.http, e).await?;
+        return Ok(());
+    } else {
+        Some(template.to_string())
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let saved =
+        db.set_guild_levelup_message(msg.guild_id.unwrap(), new_template)?;
+
+    let m = match saved.levelup_message {
+        Some(_) => "Level-up message saved",
+        None => "Level-up message reset to the default",
+    };
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+#[command("preview")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Preview the level-up message using your own stats. Pass a \
+                 message to preview it without saving it"]
+#[usage = "[message]"]
+pub async fn levelup_preview_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let (saved_template, curve, xp) = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        let guild = db.get_guild(guild_id)?;
+        let xp = db
+            .get_guild_user(msg.author.id, guild_id)
+            .map(|u| u.xp)
+            .unwrap_or(0);
+
+        let curve = guild.curve();
+
+        (guild.levelup_message, curve, xp)
+    };
+
+    let template = match args.rest().trim() {
+        "" => saved_template,
+        t => {
+            if let Err(e) = validate_template(t) {
+                msg.channel_id.say(&ctx.http, e).await?;
+                return Ok(());
+            }
+
+            Some(t.to_string())
+        },
+    };
+
+    let server = guild_id
+        .name(&ctx.cache)
+        .await
+        .unwrap_or_else(|| "this server".to_string());
+    let vars = LevelUpVars::new(&msg.author, xp, &curve, server);
+
+    let rendered = render_level_up(template.as_deref(), &vars);
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(rendered)
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("mode")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Choose where level-ups are announced: `current` (the channel \
+                 the member leveled up in), `dm`, or `off`. Use the `channel` \
+                 subcommand to announce in a specific channel"]
+#[usage = "<current|dm|off>"]
+#[example = "dm"]
+#[num_args(1)]
+pub async fn levelup_mode_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let mode = args.single::<String>().ok().and_then(|m| m.parse().ok());
+
+    let mode = match mode {
+        Some(AnnounceMode::Channel) => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Use `levelup channel <channel>` to pick the channel",
+                )
+                .await?;
+
+            return Ok(());
+        },
+        Some(mode) => mode,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "The mode must be `current`, `dm` or `off`")
+                .await?;
+
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    db.set_guild_levelup_mode(msg.guild_id.unwrap(), mode, None)?;
+
+    let m = match mode {
+        AnnounceMode::Off => "Level-ups will no longer be announced",
+        AnnounceMode::Dm => "Level-ups will be announced in DMs",
+        _ => "Level-ups will be announced where the member leveled up",
+    };
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+#[command("channel")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Announce all level-ups in one channel"]
+#[usage = "<channel>"]
+#[example = "#level-ups"]
+#[num_args(1)]
+pub async fn levelup_channel_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let channel = match args.single::<ChannelId>() {
+        Ok(id) => ctx.cache.guild_channel(id).await,
+        Err(_) => None,
+    };
+
+    let channel = match channel
+        .filter(|c| c.guild_id == guild_id && c.kind == ChannelType::Text)
+    {
+        Some(c) => c,
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown channel").await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    db.set_guild_levelup_mode(
+        guild_id,
+        AnnounceMode::Channel,
+        Some(channel.id),
+    )?;
+
+    let bot_id = ctx.cache.current_user_id().await;
+    let can_send = channel
+        .permissions_for_user(&ctx.cache, bot_id)
+        .await
+        .map(|p| p.read_messages() && p.send_messages())
+        .unwrap_or(false);
+
+    let mut m = format!("Level-ups will be announced in <#{}>", channel.id);
+
+    if !can_send {
+        m.push_str(
+            "\n:warning: I can't send messages there, so level-ups will be \
+             announced where the member leveled up until that's fixed",
+        );
+    }
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
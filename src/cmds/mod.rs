@@ -1,2 +1,14 @@
+pub mod backup;
+pub mod export;
+pub mod filters;
+pub mod import;
+pub mod levelup;
 pub mod meta;
+pub mod moderation;
+pub mod multipliers;
+pub mod privacy;
+pub mod profile;
+pub mod rewards;
+pub mod seasons;
+pub mod settings;
 pub mod xp;
@@ -0,0 +1,520 @@
+use std::{collections::HashMap, time::Duration};
+
+use diesel::OptionalExtension;
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    levelup::{grant_level_rewards, revoke_level_rewards},
+    util::{
+        curve::{LevelCurve, MAX_LEVEL},
+        format::thousands,
+        parse::parse_member,
+    },
+    PendingGuildResets,
+};
+
+/// How long a server XP reset waits to be confirmed
+pub const RESET_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[command("block")]
+#[aliases("xp_block")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Stop a member from earning XP. They keep the XP they have, \
+                 but are hidden from the leaderboard"]
+#[usage = "<member>"]
+#[example = "@spammer"]
+#[num_args(1)]
+pub async fn block_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    set_blocked(ctx, msg, args, true).await
+}
+
+#[command("unblock")]
+#[aliases("xp_unblock")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Let a blocked member earn XP again"]
+#[usage = "<member>"]
+#[example = "@spammer"]
+#[num_args(1)]
+pub async fn unblock_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    set_blocked(ctx, msg, args, false).await
+}
+
+#[command("blocked")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "List the members who are blocked from earning XP"]
+pub async fn blocked_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let blocked = db.get_blocked_guild_users(msg.guild_id.unwrap())?;
+
+    let m = match blocked.len() {
+        0 => "Nobody is blocked from earning XP".to_string(),
+        _ => format!(
+            "**Blocked from earning XP**\n{}",
+            blocked
+                .iter()
+                .map(|u| format!("<@{}>", u.user_id))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ),
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn set_blocked(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+    blocked: bool,
+) -> CommandResult {
+    let user_id = match args.single::<UserId>() {
+        Ok(u) => u,
+        Err(_) => {
+            msg.channel_id.say(&ctx.http, "Unknown member").await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let saved =
+        db.set_guild_user_blocked(user_id, msg.guild_id.unwrap(), blocked)?;
+
+    let m = if saved.blocked {
+        format!("<@{}> can no longer earn XP", user_id)
+    } else {
+        format!("<@{}> can earn XP again", user_id)
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("xp")]
+#[aliases("manage_xp")]
+#[sub_commands(
+    xp_give_cmd,
+    xp_remove_cmd,
+    xp_set_cmd,
+    xp_level_cmd,
+    xp_reset_cmd,
+    xp_reset_server_cmd
+)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Change members' XP. Reward roles are given or taken away to \
+                 match their new level"]
+pub async fn xp_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    msg.channel_id
+        .say(
+            &ctx.http,
+            "Expected `give`, `remove`, `set`, `level`, `reset` or \
+             `reset_server`",
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command("give")]
+#[aliases("add")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Give a member XP"]
+#[usage = "<member> <amount>"]
+#[example = "@someone 500"]
+#[num_args(2)]
+pub async fn xp_give_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let user = match args_member(ctx, msg, &mut args).await? {
+        Some(u) => u,
+        None => return Ok(()),
+    };
+
+    let amount = match args.single::<i32>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "The amount must be a positive number")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    change_xp(ctx, msg, &user, |xp, _| Ok(xp.saturating_add(amount))).await
+}
+
+#[command("remove")]
+#[aliases("take")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Take XP away from a member. Their XP won't go below 0"]
+#[usage = "<member> <amount>"]
+#[example = "@someone 500"]
+#[num_args(2)]
+pub async fn xp_remove_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let user = match args_member(ctx, msg, &mut args).await? {
+        Some(u) => u,
+        None => return Ok(()),
+    };
+
+    let amount = match args.single::<i32>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "The amount must be a positive number")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    change_xp(ctx, msg, &user, |xp, _| Ok((xp - amount).max(0))).await
+}
+
+#[command("set")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Set a member's XP"]
+#[usage = "<member> <xp>"]
+#[example = "@someone 1000"]
+#[num_args(2)]
+pub async fn xp_set_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let user = match args_member(ctx, msg, &mut args).await? {
+        Some(u) => u,
+        None => return Ok(()),
+    };
+
+    let xp = match args.single::<i32>() {
+        Ok(n) if n >= 0 => n,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "XP must be a number no less than 0")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    change_xp(ctx, msg, &user, |_, _| Ok(xp)).await
+}
+
+#[command("level")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Set a member's level. They get exactly the XP needed to \
+                 reach it on this server's leveling curve"]
+#[usage = "<member> <level>"]
+#[example = "@someone 10"]
+#[num_args(2)]
+pub async fn xp_level_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let user = match args_member(ctx, msg, &mut args).await? {
+        Some(u) => u,
+        None => return Ok(()),
+    };
+
+    let level = match args.single::<i32>() {
+        Ok(n) if (0..=MAX_LEVEL).contains(&n) => n,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!("The level must be between 0 and {}", MAX_LEVEL),
+                )
+                .await?;
+            return Ok(());
+        },
+    };
+
+    change_xp(ctx, msg, &user, |_, curve| {
+        match curve.xp_for_level(level) {
+            Some(xp) if xp <= i32::MAX as i64 => Ok(xp as i32),
+            _ => Err(format!(
+                "Level {} can't be reached on this server's leveling curve",
+                level
+            )),
+        }
+    })
+    .await
+}
+
+#[command("reset")]
+#[aliases("clear")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Reset a member's XP to 0"]
+#[usage = "<member>"]
+#[example = "@someone"]
+#[num_args(1)]
+pub async fn xp_reset_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let user = match args_member(ctx, msg, &mut args).await? {
+        Some(u) => u,
+        None => return Ok(()),
+    };
+
+    change_xp(ctx, msg, &user, |_, _| Ok(0)).await
+}
+
+#[command("reset_server")]
+#[aliases("reset_guild", "reset_all")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Reset everyone's XP in this server to 0. Run it once, then \
+                 again with `confirm` to go through with it"]
+#[usage = "[confirm]"]
+#[example = "confirm"]
+#[max_args(1)]
+pub async fn xp_reset_server_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let confirmed = args.rest().trim().eq_ignore_ascii_case("confirm");
+
+    let data = ctx.data.read().await;
+    let mut pending = data
+        .get::<PendingGuildResets>()
+        .expect("Expected `PendingGuildResets` in TypeMap")
+        .lock()
+        .await;
+
+    if !confirmed {
+        let members = {
+            let db = data
+                .get::<Database>()
+                .expect("Expected `Database` in TypeMap")
+                .lock()
+                .await;
+
+            db.count_leaderboard_users(guild_id)?
+        };
+
+        pending.insert(guild_id, msg.author.id);
+
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "This will reset the XP of all {} members on the \
+                     leaderboard to 0, and can't be undone. Run `xp \
+                     reset_server confirm` within {} seconds to go ahead",
+                    members,
+                    RESET_CONFIRM_TIMEOUT.as_secs()
+                ),
+            )
+            .await?;
+
+        return Ok(());
+    }
+
+    // only whoever asked for the reset can confirm it
+    if pending.get(&guild_id) != Some(&msg.author.id) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "There's no reset waiting for you to confirm. Run `xp \
+                 reset_server` first",
+            )
+            .await?;
+        return Ok(());
+    }
+
+    pending.remove(&guild_id);
+    drop(pending);
+
+    let (reset, rewards) = {
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        (
+            db.reset_guild_xp(guild_id, msg.author.id)?,
+            db.get_level_rewards(guild_id)?,
+        )
+    };
+
+    let mut m = format!("Reset the XP of {} members", reset.len());
+
+    if !rewards.is_empty() {
+        let levels = reset
+            .iter()
+            .map(|u| (UserId(u.user_id as u64), 0))
+            .collect::<HashMap<UserId, i32>>();
+
+        match revoke_level_rewards(ctx, guild_id, &levels, &rewards).await {
+            Ok(n) => m.push_str(&format!(
+                "\nReward roles were taken from {} members",
+                n
+            )),
+            Err(e) => m.push_str(&format!("\n:warning: {}", e)),
+        }
+    }
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+/// Take a member from the next argument, replying if there isn't one or
+/// they're a bot
+async fn args_member(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+) -> Result<Option<User>, SerenityError> {
+    let user = match args.single_quoted::<String>() {
+        Ok(arg) => parse_member(ctx, msg.guild_id.unwrap(), &arg).await,
+        Err(_) => None,
+    };
+
+    match user {
+        Some(u) if u.bot => {
+            msg.channel_id.say(&ctx.http, "Bots don't earn XP").await?;
+            Ok(None)
+        },
+        Some(u) => Ok(Some(u)),
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown member").await?;
+            Ok(None)
+        },
+    }
+}
+
+/// Change a member's XP to whatever `new_xp` returns for their current XP,
+/// then bring their reward roles in line with their new level
+async fn change_xp(
+    ctx: &Context,
+    msg: &Message,
+    user: &User,
+    new_xp: impl FnOnce(i32, &LevelCurve) -> Result<i32, String>,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let (old, saved, curve, rewards) = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        let curve = db.get_guild(guild_id)?.curve();
+        let old = db
+            .get_guild_user(user.id, guild_id)
+            .optional()?
+            .map_or(0, |u| u.xp);
+
+        let xp = match new_xp(old, &curve) {
+            Ok(xp) => xp,
+            Err(e) => {
+                msg.channel_id.say(&ctx.http, e).await?;
+                return Ok(());
+            },
+        };
+
+        let saved =
+            db.set_guild_user_xp(user.id, guild_id, xp, msg.author.id)?;
+
+        (old, saved, curve, db.get_level_rewards(guild_id)?)
+    };
+
+    let old_level = curve.level_for_xp(old);
+    let level = curve.level_for_xp(saved.xp);
+
+    let mut m = format!(
+        "<@!{}> now has {} XP (was {}), level {}",
+        user.id,
+        thousands(saved.xp as i64),
+        thousands(old as i64),
+        level
+    );
+
+    if level != old_level {
+        m.push_str(&format!(" (was {})", old_level));
+    }
+
+    if level > old_level {
+        let outcome =
+            grant_level_rewards(ctx, guild_id, user.id, level, &rewards).await;
+
+        if !outcome.granted.is_empty() {
+            m.push_str(&format!(
+                "\nGave them {}",
+                outcome
+                    .granted
+                    .iter()
+                    .map(|r| format!("<@&{}>", r))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+
+        for e in outcome.errors {
+            m.push_str(&format!("\n:warning: {}", e));
+        }
+    } else if level < old_level && !rewards.is_empty() {
+        let levels = HashMap::from([(user.id, level)]);
+
+        match revoke_level_rewards(ctx, guild_id, &levels, &rewards).await {
+            Ok(0) => {},
+            Ok(_) => m.push_str("\nTook away reward roles above their level"),
+            Err(e) => m.push_str(&format!("\n:warning: {}", e)),
+        }
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    models::multiplier::{MultiplierStack, MultiplierTarget},
+    util::parse::{parse_channel, parse_role},
+    MULTIPLIER_LIMIT,
+};
+
+#[command("multiplier")]
+#[aliases("multipliers", "boost")]
+#[sub_commands(
+    multiplier_role_cmd,
+    multiplier_channel_cmd,
+    multiplier_remove_cmd,
+    multiplier_stack_cmd
+)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "List the roles and channels that earn more (or less) XP"]
+pub async fn multiplier_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guild_id = msg.guild_id.unwrap();
+    let guild = db.get_guild(guild_id)?;
+    let multipliers = db.get_xp_multipliers(guild_id)?;
+
+    let mut m = format!("**XP multipliers** ({})", guild.multiplier_stack());
+
+    if multipliers.is_empty() {
+        m.push_str("\nnone");
+    }
+
+    for multiplier in multipliers {
+        m.push_str(&format!(
+            "\n{}: {}x",
+            multiplier.mention(),
+            multiplier.multiplier
+        ));
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("role")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Set the XP multiplier for members with a role"]
+#[usage = "<role> <multiplier>"]
+#[example = "@Booster 1.5"]
+#[num_args(2)]
+pub async fn multiplier_role_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let guild = match msg.guild(&ctx.cache).await {
+        Some(g) => g,
+        None => return Ok(()),
+    };
+
+    let role_id = args
+        .single_quoted::<String>()
+        .ok()
+        .and_then(|r| parse_role(&guild, &r));
+
+    let role_id = match role_id {
+        Some(r) => r,
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown role").await?;
+            return Ok(());
+        },
+    };
+
+    set_multiplier(ctx, msg, MultiplierTarget::Role, role_id.0, args).await
+}
+
+#[command("channel")]
+#[aliases("category")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Set the XP multiplier for a channel, or every channel in a \
+                 category"]
+#[usage = "<channel|category> <multiplier>"]
+#[example = "#serious-discussion 2"]
+#[num_args(2)]
+pub async fn multiplier_channel_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let channel_id = match args.single_quoted::<String>() {
+        Ok(c) => parse_channel(ctx, msg.guild_id.unwrap(), &c).await,
+        Err(_) => None,
+    };
+
+    let channel_id = match channel_id {
+        Some(c) => c,
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown channel").await?;
+            return Ok(());
+        },
+    };
+
+    set_multiplier(ctx, msg, MultiplierTarget::Channel, channel_id.0, args)
+        .await
+}
+
+#[command("remove")]
+#[aliases("delete", "rm")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Remove the XP multiplier from a role or channel"]
+#[usage = "<role|channel>"]
+#[example = "@Booster"]
+#[min_args(1)]
+pub async fn multiplier_remove_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild = match msg.guild(&ctx.cache).await {
+        Some(g) => g,
+        None => return Ok(()),
+    };
+
+    let arg = args.rest();
+
+    // deleted roles and channels can still be removed by ID
+    let target_id = match parse_role(&guild, arg) {
+        Some(r) => Some(r.0),
+        None => parse_channel(ctx, guild.id, arg)
+            .await
+            .map(|c| c.0)
+            .or_else(|| arg.trim().parse::<u64>().ok()),
+    };
+
+    let target_id = match target_id {
+        Some(t) => t,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Unknown role or channel")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let m = match db.remove_xp_multiplier(guild.id, target_id)? {
+        0 => "That role or channel doesn't have a multiplier",
+        _ => "Multiplier removed",
+    };
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+#[command("stack")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Choose how multipliers combine when more than one applies: \
+                 `multiply` (1.5x and 2x give 3x), `add` (2.5x) or `highest` \
+                 (2x)"]
+#[usage = "<multiply|add|highest>"]
+#[example = "highest"]
+#[num_args(1)]
+pub async fn multiplier_stack_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let stack = match args.single::<String>()?.parse::<MultiplierStack>() {
+        Ok(s) => s,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx.http, "Expected `multiply`, `add` or `highest`")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let saved = db.set_guild_multiplier_stack(msg.guild_id.unwrap(), stack)?;
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Multipliers will now stack using `{}`",
+                saved.multiplier_stack()
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn set_multiplier(
+    ctx: &Context,
+    msg: &Message,
+    target: MultiplierTarget,
+    target_id: u64,
+    mut args: Args,
+) -> CommandResult {
+    let multiplier = match args.single::<f32>() {
+        Ok(m) if (0.0..=MULTIPLIER_LIMIT).contains(&m) => m,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "The multiplier must be a number between 0 and {}",
+                        MULTIPLIER_LIMIT
+                    ),
+                )
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let saved = db.set_xp_multiplier(
+        msg.guild_id.unwrap(),
+        target,
+        target_id,
+        multiplier,
+    )?;
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(format!(
+                "{} now earns {}x XP",
+                saved.mention(),
+                saved.multiplier
+            ))
+        })
+        .await?;
+
+    Ok(())
+}
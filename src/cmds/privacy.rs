@@ -0,0 +1,277 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    http::AttachmentType,
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    export::EXPORT_SIZE_LIMIT,
+    models::{
+        season::SeasonStanding,
+        user::User as GuildUser,
+        user_settings::UserSettings,
+        xp_event::{XpDay, XpEvent},
+    },
+    PendingDeletions,
+};
+
+/// How long a request to erase someone's data waits to be confirmed
+pub const DELETE_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Everything stored about a user, as sent to them by `mydata`
+#[derive(Debug, Serialize)]
+struct UserData {
+    /// A string, since IDs are too big for some JSON readers
+    user_id: String,
+    exported_at: DateTime<Utc>,
+    /// The user's XP in each guild
+    guilds: Vec<GuildUser>,
+    settings: UserSettings,
+    /// Every change to the user's XP, oldest first
+    xp_history: Vec<XpEvent>,
+    /// The XP the user earned each day, for the daily, weekly and monthly
+    /// leaderboards
+    daily_xp: Vec<XpDay>,
+    /// The user's places in past seasons
+    season_standings: Vec<SeasonStanding>,
+}
+
+#[command("mydata")]
+#[aliases("my_data")]
+#[description = "Get a copy of everything the bot stores about you, sent to \
+                 your DMs"]
+pub async fn mydata_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut user_data = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        UserData {
+            user_id: msg.author.id.to_string(),
+            exported_at: Utc::now(),
+            guilds: db.get_user_rows(msg.author.id)?,
+            settings: db.get_user_settings(msg.author.id)?,
+            xp_history: db.get_user_xp_events(msg.author.id)?,
+            daily_xp: db.get_user_xp_days(msg.author.id)?,
+            season_standings: db.get_user_season_standings(msg.author.id)?,
+        }
+    };
+
+    let mut json = serde_json::to_vec_pretty(&user_data)?;
+    let mut history = Vec::new();
+
+    // the ledger is the only part that grows with every message, so it's
+    // sent in files of its own when everything won't fit in one
+    if json.len() > EXPORT_SIZE_LIMIT {
+        history = split_history(&user_data.xp_history)?;
+        user_data.xp_history.clear();
+        json = serde_json::to_vec_pretty(&user_data)?;
+    }
+
+    let mut content = format!(
+        "Here's everything the bot stores about you: your XP in {} servers, \
+         every change to it, your daily XP, your places in past seasons and \
+         your settings. Use `forgetme` to erase it",
+        user_data.guilds.len()
+    );
+
+    if !history.is_empty() {
+        content.push_str(
+            "\nThe changes to your XP are too many for one file, so they \
+             follow in files of their own",
+        );
+    }
+
+    let file = AttachmentType::Bytes {
+        data: json.into(),
+        filename: format!("free6-{}.json", msg.author.id),
+    };
+
+    let mut sent = msg
+        .author
+        .direct_message(&ctx, |m| m.content(content).add_file(file))
+        .await;
+
+    for (i, part) in history.into_iter().enumerate() {
+        if sent.is_err() {
+            break;
+        }
+
+        let file = AttachmentType::Bytes {
+            data: part.into(),
+            filename: format!(
+                "free6-{}-xp-history-{}.json",
+                msg.author.id,
+                i + 1
+            ),
+        };
+
+        sent = msg.author.direct_message(&ctx, |m| m.add_file(file)).await;
+    }
+
+    let reply = if sent.is_ok() {
+        "Sent your data to your DMs"
+    } else {
+        "Couldn't DM you. Let members of this server message you and try \
+         again"
+    };
+
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
+
+/// Split a user's XP history into JSON arrays that are each small enough to
+/// send
+fn split_history(events: &[XpEvent]) -> serde_json::Result<Vec<Vec<u8>>> {
+    let mut parts = Vec::new();
+    let mut part = b"[".to_vec();
+
+    for event in events {
+        let row = serde_json::to_vec(event)?;
+
+        // room for the comma and the closing bracket
+        if part.len() > 1 && part.len() + row.len() + 2 > EXPORT_SIZE_LIMIT {
+            part.push(b']');
+            parts.push(part);
+            part = b"[".to_vec();
+        }
+
+        if part.len() > 1 {
+            part.push(b',');
+        }
+
+        part.extend_from_slice(&row);
+    }
+
+    part.push(b']');
+    parts.push(part);
+
+    Ok(parts)
+}
+
+#[command("forgetme")]
+#[aliases("forget_me", "delete_my_data")]
+#[description = "Erase everything the bot stores about you, in every server. \
+                 Run it once, then again with `confirm` to go through with it"]
+#[usage = "[confirm]"]
+#[example = "confirm"]
+#[max_args(1)]
+pub async fn forgetme_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let confirmed = args.rest().trim().eq_ignore_ascii_case("confirm");
+
+    let data = ctx.data.read().await;
+    let mut pending = data
+        .get::<PendingDeletions>()
+        .expect("Expected `PendingDeletions` in TypeMap")
+        .lock()
+        .await;
+
+    if !confirmed {
+        pending.insert(msg.author.id, ());
+
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "This will erase your XP in every server, your settings \
+                     and your XP history, and can't be undone. Servers that \
+                     blocked you from earning XP keep the block. Run \
+                     `forgetme confirm` within {} seconds to go ahead",
+                    DELETE_CONFIRM_TIMEOUT.as_secs()
+                ),
+            )
+            .await?;
+
+        return Ok(());
+    }
+
+    if pending.remove(&msg.author.id).is_none() {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "There's nothing waiting for you to confirm. Run `forgetme` \
+                 first",
+            )
+            .await?;
+        return Ok(());
+    }
+
+    drop(pending);
+
+    let guilds = {
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        db.delete_user_data(msg.author.id)?
+    };
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Erased your data from {} servers. You'll start earning XP \
+                 from scratch if you keep chatting",
+                guilds
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command("purge_user")]
+#[owners_only]
+#[description = "Erase everything stored about a user by their ID, for \
+                 deletion requests sent outside of Discord"]
+#[usage = "<user id>"]
+#[num_args(1)]
+pub async fn purge_user_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let user_id = match args.single::<u64>() {
+        Ok(id) => UserId(id),
+        Err(_) => {
+            msg.channel_id.say(&ctx.http, "Expected a user ID").await?;
+            return Ok(());
+        },
+    };
+
+    let guilds = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        db.delete_user_data(user_id)?
+    };
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!("Erased user {}'s data from {} servers", user_id, guilds),
+        )
+        .await?;
+
+    Ok(())
+}
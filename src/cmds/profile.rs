@@ -0,0 +1,248 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    leaderboard::PAGE_SIZE,
+    util::{curve::LevelCurve, format::thousands, parse::parse_member},
+};
+
+/// The most guilds listed on a global profile
+const PROFILE_GUILD_LIMIT: usize = 15;
+
+#[command("profile")]
+#[aliases("global_rank", "grank")]
+#[sub_commands(profile_privacy_cmd)]
+#[description = "Show your XP across every server the bot is in, or someone \
+                 else's"]
+#[usage = "[member]"]
+#[example = "@someone"]
+pub async fn profile_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let user = match args.rest().trim() {
+        "" => msg.author.clone(),
+        arg => match parse_member(ctx, guild_id, arg).await {
+            Some(u) => u,
+            None => {
+                msg.channel_id.say(&ctx.http, "Unknown member").await?;
+                return Ok(());
+            },
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let global = match db.get_global_user(user.id)? {
+        Some(g) => g,
+        None => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!("**{}** hasn't earned any XP yet", user.name),
+                )
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let position = db.get_global_position(&global)?;
+    let total = db.count_global_users()?;
+    let hide_guilds = db.get_user_settings(user.id)?.hide_guilds;
+
+    // there's no guild to take a curve from, so the global level uses the
+    // default one
+    let level = LevelCurve::default()
+        .level_for_xp(global.xp.min(i32::MAX as i64) as i32);
+
+    let mut m = format!(
+        "**{}**'s global profile\n\
+         Level {} ({} XP), #{} of {}",
+        user.name,
+        level,
+        thousands(global.xp),
+        thousands(position),
+        thousands(total),
+    );
+
+    // a hidden list is only ever sent to its owner, in their DMs
+    let show_list = !hide_guilds || user.id == msg.author.id;
+    let mut list = String::new();
+
+    if show_list {
+        let guilds = db.get_user_guilds(user.id)?;
+
+        for u in guilds.iter().take(PROFILE_GUILD_LIMIT) {
+            let guild_id = GuildId(u.guild_id as u64);
+            let name = guild_id
+                .name(&ctx.cache)
+                .await
+                .unwrap_or_else(|| "Unknown server".to_string());
+            let level = db.get_guild(guild_id)?.curve().level_for_xp(u.xp);
+
+            list.push_str(&format!(
+                "\n{}: level {} ({} XP)",
+                name,
+                level,
+                thousands(u.xp as i64)
+            ));
+        }
+
+        if guilds.len() > PROFILE_GUILD_LIMIT {
+            list.push_str(&format!(
+                "\n...and {} more",
+                guilds.len() - PROFILE_GUILD_LIMIT
+            ));
+        }
+    }
+
+    if hide_guilds {
+        m.push_str(&format!(
+            "\n\nEarned in {} servers (the list is hidden)",
+            global.guilds
+        ));
+
+        if show_list {
+            let sent = msg
+                .author
+                .direct_message(ctx, |x| {
+                    x.content(format!(
+                        "**Your servers ({})**{}\n*Only you can see this \
+                         list*",
+                        global.guilds, list
+                    ))
+                })
+                .await
+                .is_ok();
+
+            if sent {
+                m.push_str("\nYour list was sent to your DMs");
+            } else {
+                m.push_str("\nAllow DMs from this server to get your list");
+            }
+        }
+    } else {
+        m.push_str(&format!("\n\n**Servers ({})**{}", global.guilds, list));
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("privacy")]
+#[aliases("hide")]
+#[description = "Choose whether other people can see which servers you've \
+                 earned XP in on your profile. `on` hides them"]
+#[usage = "<on|off>"]
+#[example = "on"]
+#[num_args(1)]
+pub async fn profile_privacy_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let hide = match args.single::<String>()?.to_lowercase().as_str() {
+        "on" | "true" | "hide" => true,
+        "off" | "false" | "show" => false,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Expected `on` or `off`")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let saved = db.set_user_hide_guilds(msg.author.id, hide)?;
+
+    let m = if saved.hide_guilds {
+        "Your server list is now hidden from other people. Your own profile \
+         sends it to your DMs"
+    } else {
+        "Your server list is now visible on your profile"
+    };
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+#[command("global")]
+#[aliases("global_leaderboard", "glb")]
+#[description = "Show the members with the most XP across every server the \
+                 bot is in"]
+#[usage = "[page]"]
+#[example = "2"]
+#[max_args(1)]
+pub async fn global_leaderboard_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let page = match args.single::<i64>() {
+        Ok(n) if n >= 1 => n,
+        Ok(_) => {
+            msg.channel_id.say(&ctx.http, "Pages start at 1").await?;
+            return Ok(());
+        },
+        Err(_) => 1,
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let pages = ((db.count_global_users()? + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    let page = page.min(pages);
+    let users = db.get_global_leaderboard((page - 1) * PAGE_SIZE, PAGE_SIZE)?;
+
+    let mut m = format!("**Global leaderboard** (page {}/{})", page, pages);
+
+    if users.is_empty() {
+        m.push_str("\nNobody has earned any XP yet");
+    }
+
+    for (i, u) in users.iter().enumerate() {
+        m.push_str(&format!(
+            "\n{}. <@!{}> ({} XP in {} servers)",
+            (page - 1) * PAGE_SIZE + i as i64 + 1,
+            u.user_id,
+            thousands(u.xp),
+            u.guilds
+        ));
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    levelup::check_reward_role,
+    util::parse::parse_role,
+};
+
+#[command("rewards")]
+#[aliases("reward", "level_roles")]
+#[sub_commands(add_reward_cmd, remove_reward_cmd)]
+#[description = "List the roles members get for reaching a level"]
+pub async fn rewards_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    match db.get_level_rewards(msg.guild_id.unwrap()) {
+        Ok(rewards) if rewards.is_empty() => {
+            msg.channel_id
+                .say(&ctx.http, "This server has no level rewards.")
+                .await?;
+        },
+        Ok(rewards) => {
+            let formatted = rewards
+                .iter()
+                .map(|r| format!("Level {}: <@&{}>", r.level, r.role_id))
+                .collect::<Vec<String>>();
+
+            msg.channel_id
+                .send_message(&ctx.http, |x| {
+                    x.allowed_mentions(|am| am.empty_parse())
+                        .content(formatted.join("\n"))
+                })
+                .await?;
+        },
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx.http, "Error getting rewards from database.")
+                .await?;
+        },
+    }
+
+    Ok(())
+}
+
+#[command("add")]
+#[aliases("set")]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Give a role to members once they reach a level"]
+#[usage = "<level> <role>"]
+#[example = "10 @Regular"]
+#[min_args(2)]
+pub async fn add_reward_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let level = match args.single::<i32>() {
+        Ok(l) if l > 0 => l,
+        _ => {
+            msg.channel_id.say(&ctx.http, "Invalid level").await?;
+            return Ok(());
+        },
+    };
+
+    let guild = match msg.guild(&ctx.cache).await {
+        Some(g) => g,
+        None => return Ok(()),
+    };
+
+    let role_id = match parse_role(&guild, args.rest()) {
+        Some(r) => r,
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown role").await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    db.add_level_reward(guild.id, role_id, level)?;
+
+    let mut reply =
+        format!("Members will get <@&{}> at level {}", role_id, level);
+
+    // save the reward anyway so it starts working once the problem is fixed
+    if let Err(e) = check_reward_role(ctx, &guild, role_id).await {
+        reply.push_str(&format!("\n:warning: {}", e));
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(reply)
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("remove")]
+#[aliases("delete", "rm")]
+#[required_permissions("MANAGE_ROLES")]
+#[description = "Stop giving a role out as a level reward"]
+#[usage = "<role>"]
+#[example = "@Regular"]
+#[min_args(1)]
+pub async fn remove_reward_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild = match msg.guild(&ctx.cache).await {
+        Some(g) => g,
+        None => return Ok(()),
+    };
+
+    // deleted roles can still be removed by ID
+    let role_id = match parse_role(&guild, args.rest())
+        .or_else(|| args.rest().parse::<RoleId>().ok())
+    {
+        Some(r) => r,
+        None => {
+            msg.channel_id.say(&ctx.http, "Unknown role").await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let m = match db.remove_level_reward(guild.id, role_id)? {
+        0 => "That role isn't a level reward".to_string(),
+        _ => format!("<@&{}> is no longer a level reward", role_id),
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use std::{collections::HashMap, time::Duration};
+
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    levelup::revoke_level_rewards,
+    PendingSeasonStarts,
+};
+
+/// How many past seasons the season command lists
+const SEASON_LIST_LIMIT: usize = 10;
+/// How long a new season waits to be confirmed
+pub const SEASON_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A new season someone asked for, waiting for them to confirm it
+pub struct PendingSeasonStart {
+    user_id: UserId,
+    keep: i32,
+    rewards: bool,
+}
+
+#[command("season")]
+#[aliases("seasons")]
+#[sub_commands(season_start_cmd)]
+#[description = "Show the current leaderboard season and the ones before it"]
+pub async fn season_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let seasons = db.get_seasons(msg.guild_id.unwrap())?;
+
+    let mut m = match seasons.first() {
+        Some(last) => format!(
+            "**Season {}** has been running since {}",
+            last.number + 1,
+            last.ended_at.format("%Y-%m-%d")
+        ),
+        None => "**Season 1** is running".to_string(),
+    };
+
+    if !seasons.is_empty() {
+        m.push_str("\n\n**Past seasons**");
+    }
+
+    for s in seasons.iter().take(SEASON_LIST_LIMIT) {
+        m.push_str(&format!(
+            "\nSeason {}: {} to {}",
+            s.number,
+            s.started_at
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "the start".to_string()),
+            s.ended_at.format("%Y-%m-%d")
+        ));
+    }
+
+    if !seasons.is_empty() {
+        m.push_str("\nUse `leaderboard season <number>` to see the standings");
+    }
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
+
+#[command("start")]
+#[aliases("new", "end")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "End the current season and start a new one. The leaderboard \
+                 is archived, then everyone's XP is reset. Give a percentage \
+                 to let members keep some of their XP, and add `rewards` to \
+                 take away reward roles members are no longer high enough \
+                 level for. Run it once, then again with `confirm` to go \
+                 through with it"]
+#[usage = "[keep %] [rewards] | confirm"]
+#[example = "10 rewards"]
+#[max_args(2)]
+pub async fn season_start_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    if !args.rest().trim().eq_ignore_ascii_case("confirm") {
+        let mut keep = 0;
+        let mut rewards = false;
+
+        for arg in args.raw() {
+            match arg.trim_end_matches('%').parse::<i32>() {
+                Ok(n) if (0..=100).contains(&n) => keep = n,
+                _ if arg.eq_ignore_ascii_case("rewards") => rewards = true,
+                _ => {
+                    msg.channel_id
+                        .say(
+                            &ctx.http,
+                            "Expected a percentage of XP to keep between 0 \
+                             and 100, and optionally `rewards`",
+                        )
+                        .await?;
+                    return Ok(());
+                },
+            }
+        }
+
+        let data = ctx.data.read().await;
+        let members = {
+            let db = data
+                .get::<Database>()
+                .expect("Expected `Database` in TypeMap")
+                .lock()
+                .await;
+
+            db.count_leaderboard_users(guild_id)?
+        };
+
+        data.get::<PendingSeasonStarts>()
+            .expect("Expected `PendingSeasonStarts` in TypeMap")
+            .lock()
+            .await
+            .insert(
+                guild_id,
+                PendingSeasonStart {
+                    user_id: msg.author.id,
+                    keep,
+                    rewards,
+                },
+            );
+
+        let mut m = format!(
+            "This will end the season and reset the XP of all {} members on \
+             the leaderboard",
+            members
+        );
+
+        if keep > 0 {
+            m.push_str(&format!(", letting them keep {}%", keep));
+        }
+
+        if rewards {
+            m.push_str(", then take away reward roles they're too low for");
+        }
+
+        m.push_str(&format!(
+            ". It can't be undone. Run `season start confirm` within {} \
+             seconds to go ahead",
+            SEASON_CONFIRM_TIMEOUT.as_secs()
+        ));
+
+        msg.channel_id.say(&ctx.http, m).await?;
+
+        return Ok(());
+    }
+
+    let PendingSeasonStart { keep, rewards, .. } = {
+        let data = ctx.data.read().await;
+        let mut pending = data
+            .get::<PendingSeasonStarts>()
+            .expect("Expected `PendingSeasonStarts` in TypeMap")
+            .lock()
+            .await;
+
+        // only whoever asked for the new season can confirm it
+        match pending.get(&guild_id) {
+            Some(p) if p.user_id == msg.author.id => {
+                pending.remove(&guild_id).unwrap()
+            },
+            _ => {
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        "There's no new season waiting for you to confirm. \
+                         Run `season start` first",
+                    )
+                    .await?;
+                return Ok(());
+            },
+        }
+    };
+
+    let (season, levels, level_rewards) = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        let season = db.end_season(guild_id, keep)?;
+
+        let curve = db.get_guild(guild_id)?.curve();
+        let levels = db
+            .get_guild_users(guild_id)?
+            .iter()
+            .map(|u| (UserId(u.user_id as u64), curve.level_for_xp(u.xp)))
+            .collect::<HashMap<UserId, i32>>();
+
+        (season, levels, db.get_level_rewards(guild_id)?)
+    };
+
+    let mut m = format!(
+        "Season {} has ended. See the final standings with `leaderboard \
+         season {}`\n",
+        season.number, season.number
+    );
+
+    if keep > 0 {
+        m.push_str(&format!("Everyone kept {}% of their XP", keep));
+    } else {
+        m.push_str("Everyone's XP was reset");
+    }
+
+    if rewards && !level_rewards.is_empty() {
+        match revoke_level_rewards(ctx, guild_id, &levels, &level_rewards).await
+        {
+            Ok(n) => m.push_str(&format!(
+                "\nReward roles were taken from {} members",
+                n
+            )),
+            Err(e) => m.push_str(&format!("\n:warning: {}", e)),
+        }
+    }
+
+    msg.channel_id.say(&ctx.http, m).await?;
+
+    Ok(())
+}
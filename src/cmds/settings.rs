@@ -0,0 +1,573 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::*,
+    prelude::*,
+};
+
+use crate::{
+    db::postgres::Database,
+    models::{
+        guild::{DecayMode, DepartedMode, Guild},
+        multiplier::XpMultiplier,
+    },
+    util::curve::LevelCurve,
+    DECAY_GRACE_LIMIT_DAYS,
+    DECAY_XP_LIMIT,
+    MESSAGE_XP_LIMIT,
+    VOICE_XP_LIMIT,
+    XP_TIMEOUT_LIMIT_SECS,
+};
+
+#[command("settings")]
+#[aliases("config")]
+#[sub_commands(
+    xp_range_cmd,
+    xp_cooldown_cmd,
+    voice_cmd,
+    curve_cmd,
+    decay_cmd,
+    departed_cmd
+)]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "View the bot's settings for this server"]
+pub async fn settings_cmd(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guild_id = msg.guild_id.unwrap();
+
+    match (db.get_guild(guild_id), db.get_xp_multipliers(guild_id)) {
+        (Ok(guild), Ok(multipliers)) => {
+            msg.channel_id
+                .send_message(&ctx.http, |x| {
+                    x.allowed_mentions(|am| am.empty_parse())
+                        .content(format_settings(&guild, &multipliers))
+                })
+                .await?;
+        },
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Error getting settings from database.")
+                .await?;
+        },
+    }
+
+    Ok(())
+}
+
+#[command("xp")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Set the range of XP members earn per message"]
+#[usage = "<min> <max>"]
+#[example = "15 25"]
+#[num_args(2)]
+pub async fn xp_range_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (min_xp, max_xp) = match (args.single::<i32>(), args.single::<i32>()) {
+        (Ok(min), Ok(max)) => (min, max),
+        _ => {
+            msg.channel_id.say(&ctx.http, "Invalid argument").await?;
+            return Ok(());
+        },
+    };
+
+    if min_xp < 0 || max_xp > MESSAGE_XP_LIMIT || min_xp > max_xp {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "The XP range must be between 0 and {}, with the minimum \
+                     no larger than the maximum",
+                    MESSAGE_XP_LIMIT
+                ),
+            )
+            .await?;
+
+        return Ok(());
+    }
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let saved = db.set_guild_xp_range(msg.guild_id.unwrap(), min_xp, max_xp)?;
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Members will now earn {}–{} XP per message",
+                saved.min_xp, saved.max_xp
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command("cooldown")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Set how many seconds members have to wait between XP grants"]
+#[usage = "<seconds>"]
+#[example = "60"]
+#[num_args(1)]
+pub async fn xp_cooldown_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let secs = match args.single::<i32>() {
+        Ok(n) if (0..=XP_TIMEOUT_LIMIT_SECS).contains(&n) => n,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "The cooldown must be between 0 and {} seconds",
+                        XP_TIMEOUT_LIMIT_SECS
+                    ),
+                )
+                .await?;
+
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let saved = db.set_guild_xp_timeout(msg.guild_id.unwrap(), secs)?;
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!("XP cooldown set to {}s", saved.xp_timeout_secs),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command("voice")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Configure XP for time spent in voice channels.\n\n\
+                 `enabled <on|off>`: whether voice earns XP at all\n\
+                 `xp <amount>`: XP per minute\n\
+                 `muted <on|off>`: stop muted or deafened members earning XP\n\
+                 `alone <on|off>`: stop members earning XP when nobody else \
+                 (except bots) is in the channel\n\
+                 `afk <on|off>`: stop the AFK channel earning XP"]
+#[usage = "<enabled|xp|muted|alone|afk> <value>"]
+#[example = "xp 5"]
+#[num_args(2)]
+pub async fn voice_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let setting = args.single::<String>()?.to_lowercase();
+    let value = args.single::<String>()?.to_lowercase();
+
+    let toggle = match value.as_str() {
+        "on" | "true" | "enable" => Some(true),
+        "off" | "false" | "disable" => Some(false),
+        _ => None,
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guild_id = msg.guild_id.unwrap();
+    let mut voice = db.get_guild(guild_id)?.voice_settings();
+
+    let field = match setting.as_str() {
+        "enabled" => &mut voice.voice_xp_enabled,
+        "muted" => &mut voice.voice_exclude_muted,
+        "alone" => &mut voice.voice_require_others,
+        "afk" => &mut voice.voice_exclude_afk,
+        "xp" => {
+            match value.parse::<i32>() {
+                Ok(n) if (0..=VOICE_XP_LIMIT).contains(&n) => {
+                    voice.voice_xp_per_minute = n;
+                },
+                _ => {
+                    msg.channel_id
+                        .say(
+                            &ctx.http,
+                            format!(
+                                "Voice XP must be between 0 and {} per minute",
+                                VOICE_XP_LIMIT
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                },
+            }
+
+            db.set_guild_voice_settings(guild_id, &voice)?;
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "Members will now earn {} XP per minute in voice",
+                        voice.voice_xp_per_minute
+                    ),
+                )
+                .await?;
+
+            return Ok(());
+        },
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Expected `enabled`, `xp`, `muted`, `alone` or `afk`",
+                )
+                .await?;
+            return Ok(());
+        },
+    };
+
+    *field = match toggle {
+        Some(t) => t,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Expected `on` or `off`")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let saved = db.set_guild_voice_settings(guild_id, &voice)?;
+
+    msg.channel_id
+        .say(&ctx.http, format_voice_settings(&saved))
+        .await?;
+
+    Ok(())
+}
+
+#[command("curve")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Choose how much XP each level costs.\n\n\
+                 `linear <xp>`: every level costs the same\n\
+                 `quadratic`: the same levels as MEE6 (the default)\n\
+                 `exponential <xp> <growth %>`: the first level costs `xp`, \
+                 and each one after costs `growth`% more\n\
+                 `table <xp> <xp> ...`: the total XP needed for level 1, 2, \
+                 and so on\n\n\
+                 Members keep their XP, so their level may change"]
+#[usage = "<linear|quadratic|exponential|table> [numbers...]"]
+#[example = "exponential 100 10"]
+#[min_args(1)]
+pub async fn curve_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let curve = match args.rest().parse::<LevelCurve>() {
+        Ok(c) => c,
+        Err(e) => {
+            msg.channel_id.say(&ctx.http, e).await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guild_id = msg.guild_id.unwrap();
+    let min_level = db.get_guild(guild_id)?.decay_min_level;
+
+    if min_level > curve.max_level() {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "XP decay stops at level {}, but that curve only goes up \
+                     to level {}. Lower it with `settings decay min_level` \
+                     first",
+                    min_level,
+                    curve.max_level()
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let saved = db.set_guild_level_curve(guild_id, &curve)?;
+
+    let preview = (1..=5)
+        .filter_map(|l| saved.curve().xp_for_level(l).map(|xp| (l, xp)))
+        .map(|(l, xp)| format!("level {}: {} XP", l, xp))
+        .collect::<Vec<String>>();
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Leveling curve set to `{}`\n{}",
+                saved.curve(),
+                preview.join("\n")
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command("decay")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Configure XP decay for members who stop taking part. Decay \
+                 is applied once a day (UTC).\n\n\
+                 `enabled <on|off>`: whether XP decays at all\n\
+                 `grace <days>`: how long members can go without earning XP \
+                 before it starts decaying\n\
+                 `percent <amount>`: lose a percentage of XP each day\n\
+                 `fixed <amount>`: lose a fixed amount of XP each day\n\
+                 `min_level <level>`: never decay below this level"]
+#[usage = "<enabled|grace|percent|fixed|min_level> <value>"]
+#[example = "percent 2"]
+#[num_args(2)]
+pub async fn decay_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let setting = args.single::<String>()?.to_lowercase();
+    let value = args.single::<String>()?.to_lowercase();
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guild_id = msg.guild_id.unwrap();
+    let guild = db.get_guild(guild_id)?;
+    let mut decay = guild.decay_settings();
+
+    let (field, range, error) = match setting.as_str() {
+        "enabled" => {
+            decay.decay_enabled = match value.as_str() {
+                "on" | "true" | "enable" => true,
+                "off" | "false" | "disable" => false,
+                _ => {
+                    msg.channel_id
+                        .say(&ctx.http, "Expected `on` or `off`")
+                        .await?;
+                    return Ok(());
+                },
+            };
+
+            let saved = db.set_guild_decay_settings(guild_id, &decay)?;
+            msg.channel_id
+                .say(&ctx.http, format_decay_settings(&saved))
+                .await?;
+
+            return Ok(());
+        },
+        "grace" => (
+            &mut decay.decay_grace_days,
+            0..=DECAY_GRACE_LIMIT_DAYS,
+            format!(
+                "The grace period must be between 0 and {} days",
+                DECAY_GRACE_LIMIT_DAYS
+            ),
+        ),
+        "percent" => {
+            decay.decay_mode = DecayMode::Percent.to_string();
+            (
+                &mut decay.decay_amount,
+                1..=100,
+                "The percentage must be between 1 and 100".to_string(),
+            )
+        },
+        "fixed" => {
+            decay.decay_mode = DecayMode::Fixed.to_string();
+            (
+                &mut decay.decay_amount,
+                1..=DECAY_XP_LIMIT,
+                format!("The amount must be between 1 and {}", DECAY_XP_LIMIT),
+            )
+        },
+        "min_level" | "floor" => {
+            let max_level = guild.curve().max_level();
+            (
+                &mut decay.decay_min_level,
+                0..=max_level,
+                format!(
+                    "The level must be between 0 and {}, the highest level \
+                     on this server's leveling curve",
+                    max_level
+                ),
+            )
+        },
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Expected `enabled`, `grace`, `percent`, `fixed` or \
+                     `min_level`",
+                )
+                .await?;
+            return Ok(());
+        },
+    };
+
+    *field = match value.parse::<i32>() {
+        Ok(n) if range.contains(&n) => n,
+        _ => {
+            msg.channel_id.say(&ctx.http, error).await?;
+            return Ok(());
+        },
+    };
+
+    let saved = db.set_guild_decay_settings(guild_id, &decay)?;
+
+    msg.channel_id
+        .say(&ctx.http, format_decay_settings(&saved))
+        .await?;
+
+    Ok(())
+}
+
+#[command("departed")]
+#[aliases("leavers")]
+#[required_permissions("MANAGE_GUILD")]
+#[description = "Choose what happens to members' XP when they leave.\n\n\
+                 `keep`: leave them on the leaderboard (the default)\n\
+                 `hide`: keep their XP, but hide them until they come back\n\
+                 `delete`: erase their XP in this server"]
+#[usage = "<keep|hide|delete>"]
+#[example = "hide"]
+#[num_args(1)]
+pub async fn departed_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let mode = match args.rest().trim().parse::<DepartedMode>() {
+        Ok(m) => m,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx.http, "Expected `keep`, `hide` or `delete`")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let saved = db.set_guild_departed_mode(msg.guild_id.unwrap(), mode)?;
+
+    msg.channel_id
+        .say(&ctx.http, format_departed_mode(&saved))
+        .await?;
+
+    Ok(())
+}
+
+fn format_settings(guild: &Guild, multipliers: &[XpMultiplier]) -> String {
+    let mut settings = format!(
+        "**Settings**\n\
+         Prefix: `{}`\n\
+         XP per message: {}–{}\n\
+         XP cooldown: {}s\n\
+         Leveling curve: `{}`\n\
+         {}\n\
+         {}\n\
+         {}\n\
+         XP multipliers ({}):",
+        guild.prefix,
+        guild.min_xp,
+        guild.max_xp,
+        guild.xp_timeout_secs,
+        guild.curve(),
+        format_voice_settings(guild),
+        format_decay_settings(guild),
+        format_departed_mode(guild),
+        guild.multiplier_stack(),
+    );
+
+    if multipliers.is_empty() {
+        settings.push_str(" none");
+    }
+
+    for m in multipliers {
+        settings.push_str(&format!("\n- {}: {}x", m.mention(), m.multiplier));
+    }
+
+    settings
+}
+
+fn format_voice_settings(guild: &Guild) -> String {
+    let on_off = |b: bool| if b { "on" } else { "off" };
+
+    format!(
+        "Voice XP: {}, {} per minute\n\
+         - Muted members excluded: {}\n\
+         - Members alone excluded: {}\n\
+         - AFK channel excluded: {}",
+        on_off(guild.voice_xp_enabled),
+        guild.voice_xp_per_minute,
+        on_off(guild.voice_exclude_muted),
+        on_off(guild.voice_require_others),
+        on_off(guild.voice_exclude_afk),
+    )
+}
+
+fn format_decay_settings(guild: &Guild) -> String {
+    let amount = match guild.decay_mode() {
+        DecayMode::Percent => format!("{}%", guild.decay_amount),
+        DecayMode::Fixed => format!("{} XP", guild.decay_amount),
+    };
+
+    format!(
+        "XP decay: {}, {} per day\n\
+         - Grace period: {} days\n\
+         - Minimum level: {}",
+        if guild.decay_enabled { "on" } else { "off" },
+        amount,
+        guild.decay_grace_days,
+        guild.decay_min_level,
+    )
+}
+
+fn format_departed_mode(guild: &Guild) -> String {
+    let mode = match guild.departed_mode() {
+        DepartedMode::Keep => "kept on the leaderboard",
+        DepartedMode::Hide => "hidden until they come back",
+        DepartedMode::Delete => "deleted",
+    };
+
+    format!("Members who leave: {}", mode)
+}
@@ -1,10 +1,46 @@
+use chrono::NaiveDate;
+use diesel::OptionalExtension;
+#[cfg(feature = "images")]
+use futures::future::join_all;
+#[cfg(feature = "images")]
+use serenity::http::AttachmentType;
 use serenity::{
     framework::standard::{macros::command, Args, CommandResult},
     model::prelude::*,
     prelude::*,
 };
 
-use crate::{db::postgres::Database, util::xp::xp_to_lvl};
+#[cfg(not(feature = "images"))]
+use crate::util::format::thousands;
+use crate::{
+    db::postgres::Database,
+    leaderboard::{
+        add_page_controls,
+        load_page,
+        load_season_page,
+        load_window_page,
+        Window,
+    },
+    util::{curve::LevelProgress, parse::parse_member},
+};
+#[cfg(feature = "images")]
+use crate::{
+    leaderboard::PAGE_SIZE,
+    render::{
+        avatar::avatar_url,
+        encode_png,
+        leaderboard::{
+            render_leaderboard,
+            LeaderboardRow,
+            LEADERBOARD_IMAGE_LIMIT,
+        },
+        rank_card::{render_rank_card, RankCard},
+    },
+    Avatars,
+};
+
+/// How many XP changes the history command shows
+const HISTORY_LIMIT: i64 = 15;
 
 #[command("set_xp")]
 #[owners_only]
@@ -22,7 +58,12 @@ pub async fn set_xp_cmd(
             .await;
 
         let saved = db
-            .set_guild_user_xp(msg.author.id, msg.guild_id.unwrap(), n as i32)
+            .set_guild_user_xp(
+                msg.author.id,
+                msg.guild_id.unwrap(),
+                n as i32,
+                msg.author.id,
+            )
             .unwrap();
 
         msg.channel_id
@@ -37,60 +78,605 @@ pub async fn set_xp_cmd(
 
 #[command("rank")]
 #[aliases("level", "levels", "ranking")]
-pub async fn rank_cmd(ctx: &Context, msg: &Message) -> CommandResult {
-    let data = ctx.data.read().await;
-    let db = data
-        .get::<Database>()
-        .expect("Expected `Database` in TypeMap")
-        .lock()
-        .await;
+#[description = "Show your level and rank, or someone else's"]
+#[usage = "[member]"]
+#[example = "@someone"]
+pub async fn rank_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
 
-    let m = match db.get_guild_user(msg.author.id, msg.guild_id.unwrap()) {
-        Ok(u) => format!("You are level {} ({} xp)", xp_to_lvl(u.xp), u.xp),
-        Err(_) => "You are not in the database. Run the `~create_user` command and try again".to_string(),
+    let user = match args.rest().trim() {
+        "" => msg.author.clone(),
+        arg => match parse_member(ctx, guild_id, arg).await {
+            Some(u) => u,
+            None => {
+                msg.channel_id.say(&ctx.http, "Unknown member").await?;
+                return Ok(());
+            },
+        },
     };
 
+    if user.bot {
+        msg.channel_id.say(&ctx.http, "Bots don't earn XP").await?;
+        return Ok(());
+    }
+
+    let (xp, position, curve) = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        let curve = db.get_guild(guild_id)?.curve();
+
+        match db.get_guild_user(user.id, guild_id).optional()? {
+            // blocked and hidden members aren't on the leaderboard
+            Some(u) if !u.blocked && u.left_at.is_none() => {
+                let position = db.get_leaderboard_position(guild_id, &u)?;
+                let total = db.count_leaderboard_users(guild_id)?;
+
+                (u.xp, Some((position, total)), curve)
+            },
+            Some(u) => (u.xp, None, curve),
+            None => (0, None, curve),
+        }
+    };
+
+    reply_rank(ctx, msg, &user, curve.progress(xp), position).await
+}
+
+#[cfg(feature = "images")]
+async fn reply_rank(
+    ctx: &Context,
+    msg: &Message,
+    user: &User,
+    progress: LevelProgress,
+    position: Option<(i64, i64)>,
+) -> CommandResult {
+    let avatars = {
+        let data = ctx.data.read().await;
+        data.get::<Avatars>()
+            .expect("Expected `Avatars` in TypeMap")
+            .clone()
+    };
+    let avatar = avatars.fetch(&avatar_url(user)).await;
+
+    // rendering takes long enough to hold up other events, so it's done on
+    // the blocking pool
+    let username = user.name.clone();
+    let discriminator = user.discriminator;
+    let png = tokio::task::spawn_blocking(move || {
+        encode_png(render_rank_card(&RankCard {
+            username: &username,
+            discriminator,
+            avatar: avatar.as_ref(),
+            rank: position,
+            progress,
+        }))
+    })
+    .await?;
+
+    let file = AttachmentType::Bytes {
+        data: png.into(),
+        filename: "rank.png".to_string(),
+    };
+
+    msg.channel_id
+        .send_files(&ctx.http, vec![file], |m| m)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "images"))]
+async fn reply_rank(
+    ctx: &Context,
+    msg: &Message,
+    user: &User,
+    progress: LevelProgress,
+    position: Option<(i64, i64)>,
+) -> CommandResult {
+    if progress.xp == 0 && position.is_none() {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!("**{}** hasn't earned any XP yet", user.name),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let mut m = match (progress.xp_to_next, progress.level_size()) {
+        (Some(to_next), Some(size)) => format!(
+            "**{}** is level {} ({} XP)\n{}/{} XP into the level, {} more to \
+             level {} ({}%)",
+            user.name,
+            progress.level,
+            thousands(progress.xp),
+            thousands(progress.xp_into_level),
+            thousands(size),
+            thousands(to_next),
+            progress.level + 1,
+            progress.percent
+        ),
+        _ => format!(
+            "**{}** is level {} ({} XP), the highest level",
+            user.name,
+            progress.level,
+            thousands(progress.xp)
+        ),
+    };
+
+    if let Some((position, total)) = position {
+        m.push_str(&format!(
+            "\nRank: #{} of {}",
+            thousands(position),
+            thousands(total)
+        ));
+    }
+
     msg.channel_id.say(&ctx.http, &m).await?;
 
     Ok(())
 }
 
+#[command("history")]
+#[aliases("xp_history", "log")]
+#[description = "Show the most recent changes to your XP, or someone else's, \
+                 and where they came from"]
+#[usage = "[member]"]
+#[example = "@someone"]
+pub async fn history_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let user = match args.rest().trim() {
+        "" => msg.author.clone(),
+        arg => match parse_member(ctx, guild_id, arg).await {
+            Some(u) => u,
+            None => {
+                msg.channel_id.say(&ctx.http, "Unknown member").await?;
+                return Ok(());
+            },
+        },
+    };
+
+    let events = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        db.get_xp_history(user.id, guild_id, HISTORY_LIMIT)?
+    };
+
+    let mut m = format!("**{}**'s recent XP changes", user.name);
+
+    if events.is_empty() {
+        m.push_str("\nNothing yet");
+    }
+
+    for e in events {
+        m.push_str(&format!(
+            "\n`{}` {:+} XP ({})",
+            e.created_at.format("%Y-%m-%d %H:%M"),
+            e.xp,
+            e.source()
+        ));
+
+        if let Some(actor) = e.actor_id {
+            m.push_str(&format!(" by <@!{}>", actor));
+        }
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse()).content(m)
+        })
+        .await?;
+
+    Ok(())
+}
+
 #[command("leaderboard")]
 #[aliases("lb", "top")]
-pub async fn leaderboard_cmd(ctx: &Context, msg: &Message) -> CommandResult {
-    let data = ctx.data.read().await;
-    let db = data
-        .get::<Database>()
-        .expect("Expected `Database` in TypeMap")
-        .lock()
-        .await;
-
-    match db.top_n_guild_user_xp(msg.guild_id.unwrap(), 10) {
-        Ok(users) => {
-            // TODO: figure out cache?
-            // TODO: create row on chat
-
-            let formatted = users
-                .iter()
-                .enumerate()
-                .map(|(i, u)| {
-                    format!("{}. <@!{}> ({})", i + 1, u.user_id, u.xp)
-                })
-                .collect::<Vec<String>>();
+#[cfg_attr(
+    feature = "images",
+    sub_commands(
+        leaderboard_today_cmd,
+        leaderboard_week_cmd,
+        leaderboard_month_cmd,
+        leaderboard_range_cmd,
+        leaderboard_season_cmd,
+        leaderboard_image_cmd
+    )
+)]
+#[cfg_attr(
+    not(feature = "images"),
+    sub_commands(
+        leaderboard_today_cmd,
+        leaderboard_week_cmd,
+        leaderboard_month_cmd,
+        leaderboard_range_cmd,
+        leaderboard_season_cmd
+    )
+)]
+#[description = "Show the members with the most XP. Use the reactions to \
+                 change pages"]
+#[usage = "[page]"]
+#[example = "3"]
+#[max_args(1)]
+pub async fn leaderboard_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let page = match args.single::<i64>() {
+        Ok(n) if n >= 1 => n,
+        Ok(_) => {
+            msg.channel_id.say(&ctx.http, "Pages start at 1").await?;
+            return Ok(());
+        },
+        Err(_) => 1,
+    };
 
+    let page = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        load_page(&db, msg.guild_id.unwrap(), page)
+    };
+
+    let page = match page {
+        Ok(p) => p,
+        Err(_) => {
             msg.channel_id
-                .send_message(&ctx.http, |x| {
-                    x.allowed_mentions(|am| am.empty_parse())
-                        .content(formatted.join("\n"))
-                })
+                .say(&ctx.http, "Error getting users from database.")
                 .await?;
+            return Ok(());
         },
+    };
+
+    let sent = msg
+        .channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse())
+                .content(page.content())
+        })
+        .await?;
+
+    add_page_controls(ctx, &sent, page).await?;
+
+    Ok(())
+}
+
+#[command("today")]
+#[aliases("day", "daily")]
+#[description = "Show the members who earned the most XP today (UTC)"]
+#[usage = "[page]"]
+#[example = "2"]
+#[max_args(1)]
+pub async fn leaderboard_today_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    send_window_page(ctx, msg, Window::Today, args).await
+}
+
+#[command("week")]
+#[aliases("weekly")]
+#[description = "Show the members who earned the most XP since Monday (UTC)"]
+#[usage = "[page]"]
+#[example = "2"]
+#[max_args(1)]
+pub async fn leaderboard_week_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    send_window_page(ctx, msg, Window::Week, args).await
+}
+
+#[command("month")]
+#[aliases("monthly")]
+#[description = "Show the members who earned the most XP since the 1st of \
+                 the month (UTC)"]
+#[usage = "[page]"]
+#[example = "2"]
+#[max_args(1)]
+pub async fn leaderboard_month_cmd(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+) -> CommandResult {
+    send_window_page(ctx, msg, Window::Month, args).await
+}
+
+#[command("range")]
+#[aliases("between")]
+#[description = "Show the members who earned the most XP between two days \
+                 (UTC, inclusive)"]
+#[usage = "<from> <to> [page]"]
+#[example = "2021-03-01 2021-03-07"]
+#[min_args(2)]
+#[max_args(3)]
+pub async fn leaderboard_range_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let parse_day = |s: String| NaiveDate::parse_from_str(&s, "%Y-%m-%d");
+
+    let (from, to) = match (
+        args.single::<String>().map(parse_day),
+        args.single::<String>().map(parse_day),
+    ) {
+        (Ok(Ok(from)), Ok(Ok(to))) if from <= to => (from, to),
+        (Ok(Ok(_)), Ok(Ok(_))) => {
+            msg.channel_id
+                .say(&ctx.http, "The first day must come before the second")
+                .await?;
+            return Ok(());
+        },
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Days must look like `2021-03-01`")
+                .await?;
+            return Ok(());
+        },
+    };
+
+    send_window_page(ctx, msg, Window::Range(from, to), args).await
+}
+
+#[command("season")]
+#[description = "Show the final standings of a past season, or the last one \
+                 if no number is given"]
+#[usage = "[season] [page]"]
+#[example = "3"]
+#[max_args(2)]
+pub async fn leaderboard_season_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let number = args.single::<i32>().ok();
+    let page = match args.single::<i64>() {
+        Ok(n) if n >= 1 => n,
+        Ok(_) => {
+            msg.channel_id.say(&ctx.http, "Pages start at 1").await?;
+            return Ok(());
+        },
+        Err(_) => 1,
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let page = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        let season = match number {
+            Some(n) => db.get_season(guild_id, n)?,
+            None => db.get_seasons(guild_id)?.into_iter().next(),
+        };
+
+        match season {
+            Some(s) => load_season_page(&db, guild_id, s.id, s.number, page)?,
+            None => {
+                let m = match number {
+                    Some(_) => "That season hasn't ended yet",
+                    None => "No seasons have ended yet",
+                };
+
+                msg.channel_id.say(&ctx.http, m).await?;
+                return Ok(());
+            },
+        }
+    };
+
+    let sent = msg
+        .channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse())
+                .content(page.content())
+        })
+        .await?;
+
+    add_page_controls(ctx, &sent, page).await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "images")]
+#[command("image")]
+#[aliases("img", "card")]
+#[description = "Show the members with the most XP as an image"]
+#[usage = "[count]"]
+#[example = "5"]
+#[max_args(1)]
+pub async fn leaderboard_image_cmd(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let count = match args.single::<i64>() {
+        Ok(n) if (1..=LEADERBOARD_IMAGE_LIMIT).contains(&n) => n,
+        Ok(_) => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "The image can show between 1 and {} members",
+                        LEADERBOARD_IMAGE_LIMIT
+                    ),
+                )
+                .await?;
+            return Ok(());
+        },
+        Err(_) => PAGE_SIZE,
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let (users, curve, avatars) = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        let avatars = data
+            .get::<Avatars>()
+            .expect("Expected `Avatars` in TypeMap")
+            .clone();
+
+        (
+            db.get_leaderboard_page(guild_id, 0, count)?,
+            db.get_guild(guild_id)?.curve(),
+            avatars,
+        )
+    };
+
+    if users.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Nobody has earned any XP yet")
+            .await?;
+        return Ok(());
+    }
+
+    let _typing = msg.channel_id.start_typing(&ctx.http);
+
+    // every member is looked up and has their avatar fetched at the same
+    // time, rather than waiting on each one in turn
+    let profiles = join_all(users.iter().map(|u| {
+        let avatars = &avatars;
+        let progress = curve.progress(u.xp);
+
+        async move {
+            let user_id = UserId(u.user_id as u64);
+            let user = match ctx.cache.user(user_id).await {
+                Some(user) => Some(user),
+                None => ctx.http.get_user(user_id.0).await.ok(),
+            };
+
+            let avatar = match &user {
+                Some(user) => avatars.fetch(&avatar_url(user)).await,
+                None => None,
+            };
+            let name = user
+                .map(|user| user.name)
+                .unwrap_or_else(|| "Unknown user".to_string());
+
+            (name, avatar, progress)
+        }
+    }))
+    .await;
+
+    let server = guild_id
+        .name(&ctx.cache)
+        .await
+        .unwrap_or_else(|| "this server".to_string());
+
+    // drawn on the blocking pool, like the rank card
+    let png = tokio::task::spawn_blocking(move || {
+        let rows = profiles
+            .iter()
+            .enumerate()
+            .map(|(i, (name, avatar, progress))| LeaderboardRow {
+                rank: i as i64 + 1,
+                username: name,
+                avatar: avatar.as_ref(),
+                progress: *progress,
+            })
+            .collect::<Vec<LeaderboardRow>>();
+
+        encode_png(render_leaderboard(
+            &format!("Top members in {}", server),
+            &rows,
+        ))
+    })
+    .await?;
+
+    let file = AttachmentType::Bytes {
+        data: png.into(),
+        filename: "leaderboard.png".to_string(),
+    };
+
+    msg.channel_id
+        .send_files(&ctx.http, vec![file], |m| m)
+        .await?;
+
+    Ok(())
+}
+
+/// Send a page of a windowed leaderboard, taking the page number from the
+/// next argument
+async fn send_window_page(
+    ctx: &Context,
+    msg: &Message,
+    window: Window,
+    mut args: Args,
+) -> CommandResult {
+    let page = match args.single::<i64>() {
+        Ok(n) if n >= 1 => n,
+        Ok(_) => {
+            msg.channel_id.say(&ctx.http, "Pages start at 1").await?;
+            return Ok(());
+        },
+        Err(_) => 1,
+    };
+
+    let page = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        load_window_page(&db, msg.guild_id.unwrap(), window, page)
+    };
+
+    let page = match page {
+        Ok(p) => p,
         Err(_) => {
             msg.channel_id
                 .say(&ctx.http, "Error getting users from database.")
                 .await?;
+            return Ok(());
         },
-    }
+    };
+
+    let sent = msg
+        .channel_id
+        .send_message(&ctx.http, |x| {
+            x.allowed_mentions(|am| am.empty_parse())
+                .content(page.content())
+        })
+        .await?;
+
+    add_page_controls(ctx, &sent, page).await?;
 
     Ok(())
 }
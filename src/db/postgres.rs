@@ -1,20 +1,65 @@
+use chrono::{DateTime, NaiveDate, Utc};
 use diesel::{
+    connection::SimpleConnection,
+    dsl::sql,
+    pg::Pg,
     prelude::*,
-    r2d2::{ConnectionManager, Pool},
+    r2d2::{ConnectionManager, Pool, PooledConnection},
     result::Error as DieselError,
+    sql_types::{
+        Array,
+        BigInt,
+        Bool,
+        Date,
+        Integer,
+        Nullable,
+        Text,
+        Timestamptz,
+    },
     PgConnection,
     QueryDsl,
     RunQueryDsl,
 };
-use serenity::model::id::{GuildId, UserId};
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
 
 use super::redis::RedisCache;
 use crate::{
+    backup::{GuildBackup, BACKUP_VERSION},
+    import::ImportMode,
     models::{
-        guild::{Guild, NewGuild},
-        user::{NewUser, User},
+        guild::{
+            AnnounceMode,
+            DecaySettings,
+            DepartedMode,
+            Guild,
+            NewGuild,
+            VoiceSettings,
+            XpFilters,
+        },
+        multiplier::{
+            MultiplierStack,
+            MultiplierTarget,
+            NewXpMultiplier,
+            XpMultiplier,
+        },
+        reward::{LevelReward, NewLevelReward},
+        season::{NewSeason, Season, SeasonStanding},
+        user::{GlobalUser, NewUser, RankedUser, User, WindowUser},
+        user_settings::UserSettings,
+        xp_event::{XpDay, XpEvent, XpSource},
+    },
+    schema::{
+        guilds,
+        level_rewards,
+        season_standings,
+        seasons,
+        user_settings,
+        users,
+        xp_daily,
+        xp_events,
+        xp_multipliers,
     },
-    schema::{guilds, users},
+    util::curve::LevelCurve,
 };
 
 /// The main DB for the bot
@@ -23,6 +68,37 @@ pub struct Database {
     redis: RedisCache,
 }
 
+/// A guild's users being read from one snapshot, a batch at a time. It holds
+/// on to its own connection, so the `Database` doesn't have to be locked
+/// between batches
+pub struct GuildUsersCursor {
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+}
+
+impl GuildUsersCursor {
+    /// Get the next `n` users, or fewer once the end is reached
+    ///
+    /// # SQL:
+    /// ```sql
+    /// FETCH <n> FROM guild_users;
+    /// ```
+    pub fn next_batch(
+        &mut self,
+        n: i64,
+    ) -> Result<Vec<RankedUser>, DieselError> {
+        diesel::sql_query(format!("FETCH {} FROM guild_users", n))
+            .get_results(&self.conn)
+    }
+}
+
+impl Drop for GuildUsersCursor {
+    fn drop(&mut self) {
+        // ending the transaction closes the cursor, so the connection goes
+        // back to the pool clean
+        self.conn.batch_execute("ROLLBACK").ok();
+    }
+}
+
 impl Database {
     pub fn new(database_url: &str, redis: RedisCache) -> Self {
         let manager = ConnectionManager::<PgConnection>::new(database_url);
@@ -115,28 +191,110 @@ impl Database {
             .get_results(&self.pool.get().unwrap())
     }
 
-    /// Set an *existing* user's XP
+    /// Start reading every one of a guild's users in leaderboard order, with
+    /// their rank. Blocked and hidden users are included without a rank. The
+    /// whole read comes from one snapshot, so XP earned while it's going on
+    /// can't move anyone between batches
     ///
     /// # SQL:
     /// ```sql
-    /// UPDATE users
-    /// SET xp = <xp>
-    /// WHERE guild_id = <guild_id> and user_id = <user_id>;
+    /// BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY;
+    ///
+    /// DECLARE guild_users NO SCROLL CURSOR FOR
+    /// SELECT *, CASE WHEN NOT blocked AND left_at IS NULL
+    ///     THEN ROW_NUMBER() OVER (
+    ///         PARTITION BY blocked OR left_at IS NOT NULL
+    ///         ORDER BY xp DESC, user_id DESC
+    ///     ) END AS rank
+    /// FROM users
+    /// WHERE guild_id = <guild_id>
+    /// ORDER BY xp DESC, user_id DESC;
+    /// ```
+    pub fn read_guild_users(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<GuildUsersCursor, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        conn.batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")?;
+
+        let cursor = GuildUsersCursor { conn };
+
+        diesel::sql_query(
+            "DECLARE guild_users NO SCROLL CURSOR FOR \
+             SELECT *, CASE WHEN NOT blocked AND left_at IS NULL \
+                 THEN ROW_NUMBER() OVER ( \
+                     PARTITION BY blocked OR left_at IS NOT NULL \
+                     ORDER BY xp DESC, user_id DESC \
+                 ) END AS rank \
+             FROM users \
+             WHERE guild_id = $1 \
+             ORDER BY xp DESC, user_id DESC",
+        )
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .execute(&cursor.conn)?;
+
+        Ok(cursor)
+    }
+
+    /// Set a user's XP, creating their row if they don't have one yet, and
+    /// record the difference in the ledger as a change made by `actor`
+    ///
+    /// # SQL:
+    /// ```sql
+    /// WITH old AS (
+    ///     SELECT xp FROM users
+    ///     WHERE guild_id = <guild_id> AND user_id = <user_id>
+    /// ), u AS (
+    ///     INSERT INTO users (user_id, guild_id, xp, blocked)
+    ///     VALUES (...)
+    ///     ON CONFLICT (user_id, guild_id)
+    ///     DO
+    ///         UPDATE SET xp = <xp>
+    ///     RETURNING *
+    /// ), e AS (
+    ///     INSERT INTO xp_events (guild_id, user_id, xp, source, actor_id)
+    ///     SELECT guild_id, user_id, u.xp - <old xp or 0>, 'admin', <actor>
+    ///     FROM u
+    ///     WHERE u.xp <> <old xp or 0>
+    /// )
+    /// SELECT * FROM u;
     /// ```
     pub fn set_guild_user_xp(
         &self,
         user_id: UserId,
         guild_id: GuildId,
         xp: i32,
+        actor: UserId,
     ) -> Result<User, DieselError> {
         self.redis.del_user(&guild_id, &user_id);
 
-        let user = diesel::update(
-            users::table
-                .filter(users::guild_id.eq(guild_id.0 as i64))
-                .filter(users::user_id.eq(user_id.0 as i64)),
+        // every part of the statement sees the table from before the update,
+        // so `old` still has the previous XP
+        let user = diesel::sql_query(
+            "WITH old AS ( \
+                 SELECT COALESCE(MAX(xp), 0) AS xp FROM users \
+                 WHERE user_id = $1 AND guild_id = $2 \
+             ), u AS ( \
+                 INSERT INTO users (user_id, guild_id, xp, blocked) \
+                 VALUES ($1, $2, $3, false) \
+                 ON CONFLICT (user_id, guild_id) \
+                 DO UPDATE SET xp = EXCLUDED.xp \
+                 RETURNING * \
+             ), e AS ( \
+                 INSERT INTO xp_events \
+                     (guild_id, user_id, xp, source, actor_id) \
+                 SELECT u.guild_id, u.user_id, u.xp - old.xp, $4, $5 \
+                 FROM u, old \
+                 WHERE u.xp <> old.xp \
+             ) \
+             SELECT * FROM u",
         )
-        .set(users::xp.eq(xp))
+        .bind::<BigInt, _>(user_id.0 as i64)
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .bind::<Integer, _>(xp)
+        .bind::<Text, _>(XpSource::Admin.as_str())
+        .bind::<BigInt, _>(actor.0 as i64)
         .get_result(&self.pool.get().unwrap())?;
 
         self.redis.set_user(&user);
@@ -144,7 +302,174 @@ impl Database {
         Ok(user)
     }
 
-    /// Update a user's XP or create a row in the users table
+    /// Import the XP members had on another bot, creating rows for anyone who
+    /// doesn't have one. Members who already have XP are merged by `mode`,
+    /// and every change is recorded in the ledger as made by `actor`. It's a
+    /// single statement, so either everyone is imported or nobody is.
+    /// Returns the users that were imported
+    ///
+    /// # SQL:
+    /// ```sql
+    /// WITH input AS (
+    ///     SELECT * FROM UNNEST(<user_ids>, <xps>) AS input (user_id, xp)
+    /// ), old AS (
+    ///     SELECT users.user_id, users.xp FROM users JOIN input USING (user_id)
+    ///     WHERE guild_id = <guild_id>
+    /// ), u AS (
+    ///     INSERT INTO users (user_id, guild_id, xp, blocked)
+    ///     SELECT user_id, <guild_id>, xp, false FROM input
+    ///     ON CONFLICT (user_id, guild_id)
+    ///     DO UPDATE SET xp = <EXCLUDED.xp merged by mode>
+    ///     RETURNING *
+    /// ), e AS (
+    ///     INSERT INTO xp_events (guild_id, user_id, xp, source, actor_id)
+    ///     SELECT u.guild_id, u.user_id, u.xp - COALESCE(old.xp, 0), ...
+    ///     FROM u LEFT JOIN old USING (user_id)
+    ///     WHERE u.xp <> COALESCE(old.xp, 0)
+    /// )
+    /// SELECT * FROM u;
+    /// ```
+    pub fn import_guild_users(
+        &self,
+        guild_id: GuildId,
+        imported: &[(UserId, i32)],
+        mode: ImportMode,
+        actor: UserId,
+    ) -> Result<Vec<User>, DieselError> {
+        let (user_ids, xps): (Vec<i64>, Vec<i32>) =
+            imported.iter().map(|(u, xp)| (u.0 as i64, *xp)).unzip();
+
+        // every part of the statement sees the table from before the upsert,
+        // so `old` still has the previous XP
+        let users: Vec<User> = diesel::sql_query(
+            "WITH input AS ( \
+                 SELECT * FROM UNNEST($2::BIGINT[], $3::INTEGER[]) \
+                     AS input (user_id, xp) \
+             ), old AS ( \
+                 SELECT users.user_id, users.xp \
+                 FROM users JOIN input USING (user_id) \
+                 WHERE users.guild_id = $1 \
+             ), u AS ( \
+                 INSERT INTO users (user_id, guild_id, xp, blocked) \
+                 SELECT user_id, $1, xp, false FROM input \
+                 ON CONFLICT (user_id, guild_id) \
+                 DO UPDATE SET xp = CASE $4 \
+                     WHEN 'add' THEN LEAST( \
+                         users.xp::BIGINT + EXCLUDED.xp, 2147483647 \
+                     )::INTEGER \
+                     WHEN 'max' THEN GREATEST(users.xp, EXCLUDED.xp) \
+                     ELSE EXCLUDED.xp \
+                 END \
+                 RETURNING * \
+             ), e AS ( \
+                 INSERT INTO xp_events \
+                     (guild_id, user_id, xp, source, actor_id) \
+                 SELECT u.guild_id, u.user_id, \
+                     u.xp - COALESCE(old.xp, 0), $5, $6 \
+                 FROM u LEFT JOIN old USING (user_id) \
+                 WHERE u.xp <> COALESCE(old.xp, 0) \
+             ) \
+             SELECT * FROM u",
+        )
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .bind::<Array<BigInt>, _>(user_ids)
+        .bind::<Array<Integer>, _>(xps)
+        .bind::<Text, _>(mode.as_str())
+        .bind::<Text, _>(XpSource::Import.as_str())
+        .bind::<BigInt, _>(actor.0 as i64)
+        .get_results(&self.pool.get().unwrap())?;
+
+        for user in &users {
+            self.redis.del_user(&guild_id, &UserId(user.user_id as u64));
+        }
+
+        Ok(users)
+    }
+
+    /// Update a user's XP or create a row in the users table, and record the
+    /// grant in the ledger. XP from a source that [`XpSource::is_earned`] is
+    /// also added to the daily totals, and shows a member who was hidden
+    /// after leaving on the leaderboard again. Returns `None` without
+    /// changing anything if the user is blocked
+    ///
+    /// # SQL:
+    /// ```sql
+    /// WITH u AS (
+    ///     INSERT INTO users (user_id, guild_id, xp, blocked)
+    ///     VALUES (...)
+    ///     ON CONFLICT (user_id, guild_id)
+    ///     DO
+    ///         UPDATE SET xp = users.xp + <xp>, last_xp_at = <now if earned>,
+    ///             left_at = <NULL if earned>
+    ///         WHERE NOT users.blocked
+    ///     RETURNING *
+    /// ), e AS (
+    ///     INSERT INTO xp_events (guild_id, user_id, xp, source)
+    ///     SELECT guild_id, user_id, <xp>, <source> FROM u
+    /// ), d AS (
+    ///     INSERT INTO xp_daily (guild_id, day, user_id, xp)
+    ///     SELECT guild_id, <today>, user_id, <xp> FROM u
+    ///     WHERE <source is earned>
+    ///     ON CONFLICT (guild_id, day, user_id)
+    ///     DO
+    ///         UPDATE SET xp = xp_daily.xp + <xp>
+    /// )
+    /// SELECT * FROM u;
+    /// ```
+    pub fn add_guild_user_xp(
+        &self,
+        user_id: UserId,
+        guild_id: GuildId,
+        xp: i32,
+        source: XpSource,
+    ) -> Result<Option<User>, DieselError> {
+        self.redis.del_user(&guild_id, &user_id);
+
+        // diesel 1.x can't put a WHERE on ON CONFLICT DO UPDATE. The history
+        // is written in the same statement so it only counts granted XP
+        let user = diesel::sql_query(
+            "WITH u AS ( \
+                 INSERT INTO users (user_id, guild_id, xp, blocked) \
+                 VALUES ($1, $2, $3, false) \
+                 ON CONFLICT (user_id, guild_id) \
+                 DO UPDATE SET xp = users.xp + EXCLUDED.xp, \
+                     last_xp_at = CASE WHEN $5 THEN NOW() \
+                         ELSE users.last_xp_at END, \
+                     left_at = CASE WHEN $5 THEN NULL \
+                         ELSE users.left_at END \
+                 WHERE NOT users.blocked \
+                 RETURNING * \
+             ), e AS ( \
+                 INSERT INTO xp_events (guild_id, user_id, xp, source) \
+                 SELECT guild_id, user_id, $3, $4 FROM u \
+             ), d AS ( \
+                 INSERT INTO xp_daily (guild_id, day, user_id, xp) \
+                 SELECT guild_id, (NOW() AT TIME ZONE 'UTC')::DATE, \
+                     user_id, $3 \
+                 FROM u \
+                 WHERE $5 \
+                 ON CONFLICT (guild_id, day, user_id) \
+                 DO UPDATE SET xp = xp_daily.xp + EXCLUDED.xp \
+             ) \
+             SELECT * FROM u",
+        )
+        .bind::<BigInt, _>(user_id.0 as i64)
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .bind::<Integer, _>(xp)
+        .bind::<Text, _>(source.as_str())
+        .bind::<Bool, _>(source.is_earned())
+        .get_result::<User>(&self.pool.get().unwrap())
+        .optional()?;
+
+        if let Some(user) = &user {
+            self.redis.set_user(user);
+        }
+
+        Ok(user)
+    }
+
+    /// Block or unblock a user from earning XP, creating their row if they
+    /// don't have one yet
     ///
     /// # SQL:
     /// ```sql
@@ -152,28 +477,28 @@ impl Database {
     /// VALUES (...)
     /// ON CONFLICT (user_id, guild_id)
     /// DO
-    ///     UPDATE SET xp = <xp>;
+    ///     UPDATE SET blocked = <blocked>;
     /// ```
-    pub fn add_guild_user_xp(
+    pub fn set_guild_user_blocked(
         &self,
         user_id: UserId,
         guild_id: GuildId,
-        xp: i32,
+        blocked: bool,
     ) -> Result<User, DieselError> {
         self.redis.del_user(&guild_id, &user_id);
 
         let new_user = NewUser {
             user_id: user_id.0 as i64,
             guild_id: guild_id.0 as i64,
-            blocked: false,
-            xp,
+            blocked,
+            xp: 0,
         };
 
         let user = diesel::insert_into(users::table)
             .values(&new_user)
             .on_conflict((users::user_id, users::guild_id))
             .do_update()
-            .set(users::xp.eq(users::xp + xp))
+            .set(users::blocked.eq(blocked))
             .get_result(&self.pool.get().unwrap())?;
 
         self.redis.set_user(&user);
@@ -181,18 +506,237 @@ impl Database {
         Ok(user)
     }
 
-    pub fn top_n_guild_user_xp(
+    /// Hide a member who left a guild from its leaderboards, or show them
+    /// again when they come back. Their XP is kept either way
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE users
+    /// SET left_at = <NOW() if left, otherwise NULL>
+    /// WHERE user_id = <user_id> AND guild_id = <guild_id>
+    ///     AND left_at IS <NULL if left, otherwise NOT NULL>;
+    /// ```
+    pub fn set_guild_user_left(
+        &self,
+        user_id: UserId,
+        guild_id: GuildId,
+        left: bool,
+    ) -> Result<(), DieselError> {
+        self.redis.del_user(&guild_id, &user_id);
+
+        let user = users::table
+            .filter(users::user_id.eq(user_id.0 as i64))
+            .filter(users::guild_id.eq(guild_id.0 as i64));
+
+        // only rows that change are touched, so leaving twice keeps the
+        // first time
+        if left {
+            diesel::update(user.filter(users::left_at.is_null()))
+                .set(users::left_at.eq(Utc::now()))
+                .execute(&self.pool.get().unwrap())?;
+        } else {
+            diesel::update(user.filter(users::left_at.is_not_null()))
+                .set(users::left_at.eq(None::<DateTime<Utc>>))
+                .execute(&self.pool.get().unwrap())?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete a member's XP in a guild they left, along with their daily
+    /// totals there. The XP they lose is recorded in the ledger
+    ///
+    /// # SQL:
+    /// ```sql
+    /// WITH u AS (
+    ///     DELETE FROM users
+    ///     WHERE user_id = <user_id> AND guild_id = <guild_id>
+    ///     RETURNING *
+    /// ), e AS (
+    ///     INSERT INTO xp_events (guild_id, user_id, xp, source)
+    ///     SELECT guild_id, user_id, -xp, 'left' FROM u
+    ///     WHERE xp <> 0
+    /// ), d AS (
+    ///     DELETE FROM xp_daily
+    ///     WHERE guild_id = <guild_id> AND user_id = <user_id>
+    /// )
+    /// SELECT * FROM u;
+    /// ```
+    pub fn delete_guild_user(
+        &self,
+        user_id: UserId,
+        guild_id: GuildId,
+    ) -> Result<Option<User>, DieselError> {
+        self.redis.del_user(&guild_id, &user_id);
+
+        diesel::sql_query(
+            "WITH u AS ( \
+                 DELETE FROM users \
+                 WHERE user_id = $1 AND guild_id = $2 \
+                 RETURNING * \
+             ), e AS ( \
+                 INSERT INTO xp_events (guild_id, user_id, xp, source) \
+                 SELECT guild_id, user_id, -xp, $3 FROM u \
+                 WHERE xp <> 0 \
+             ), d AS ( \
+                 DELETE FROM xp_daily \
+                 WHERE guild_id = $2 AND user_id = $1 \
+             ) \
+             SELECT * FROM u",
+        )
+        .bind::<BigInt, _>(user_id.0 as i64)
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .bind::<Text, _>(XpSource::Left.as_str())
+        .get_result::<User>(&self.pool.get().unwrap())
+        .optional()
+    }
+
+    /// Get every user blocked from earning XP in a guild
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM users
+    /// WHERE guild_id = <guild_id> AND blocked;
+    /// ```
+    pub fn get_blocked_guild_users(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<User>, DieselError> {
+        users::table
+            .filter(users::guild_id.eq(guild_id.0 as i64))
+            .filter(users::blocked.eq(true))
+            .order(users::user_id)
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Count the users who show up on a guild's leaderboard
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT COUNT(*) FROM users
+    /// WHERE guild_id = <guild_id> AND NOT blocked AND left_at IS NULL;
+    /// ```
+    pub fn count_leaderboard_users(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<i64, DieselError> {
+        users::table
+            .filter(users::guild_id.eq(guild_id.0 as i64))
+            .filter(users::blocked.eq(false))
+            .filter(users::left_at.is_null())
+            .count()
+            .get_result(&self.pool.get().unwrap())
+    }
+
+    /// Get a user's position on a guild's leaderboard, starting at 1. Ties
+    /// are broken the same way as on the leaderboard
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT COUNT(*) + 1 FROM users
+    /// WHERE guild_id = <guild_id> AND NOT blocked AND left_at IS NULL
+    ///     AND (xp, user_id) > (<xp>, <user_id>);
+    /// ```
+    pub fn get_leaderboard_position(
+        &self,
+        guild_id: GuildId,
+        user: &User,
+    ) -> Result<i64, DieselError> {
+        let above: i64 = users::table
+            .filter(users::guild_id.eq(guild_id.0 as i64))
+            .filter(users::blocked.eq(false))
+            .filter(users::left_at.is_null())
+            .filter(leaderboard_cmp(">", (user.xp, user.user_id)))
+            .count()
+            .get_result(&self.pool.get().unwrap())?;
+
+        Ok(above + 1)
+    }
+
+    /// Get `n` users from a guild's leaderboard, skipping the first `offset`.
+    /// Used to jump straight to a page
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM users
+    /// WHERE guild_id = <guild_id> AND NOT blocked AND left_at IS NULL
+    /// ORDER BY xp DESC, user_id DESC
+    /// LIMIT <n> OFFSET <offset>;
+    /// ```
+    pub fn get_leaderboard_page(
         &self,
         guild_id: GuildId,
+        offset: i64,
         n: i64,
     ) -> Result<Vec<User>, DieselError> {
         users::table
             .filter(users::guild_id.eq(guild_id.0 as i64))
-            .order(users::xp.desc())
+            .filter(users::blocked.eq(false))
+            .filter(users::left_at.is_null())
+            .order((users::xp.desc(), users::user_id.desc()))
+            .offset(offset)
+            .limit(n)
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Get the `n` users ranked just below `(xp, user_id)` on a guild's
+    /// leaderboard. Unlike an offset this stays fast on deep pages
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM users
+    /// WHERE guild_id = <guild_id> AND NOT blocked AND left_at IS NULL
+    ///     AND (xp, user_id) < (<xp>, <user_id>)
+    /// ORDER BY xp DESC, user_id DESC
+    /// LIMIT <n>;
+    /// ```
+    pub fn get_leaderboard_after(
+        &self,
+        guild_id: GuildId,
+        (xp, user_id): (i32, i64),
+        n: i64,
+    ) -> Result<Vec<User>, DieselError> {
+        users::table
+            .filter(users::guild_id.eq(guild_id.0 as i64))
+            .filter(users::blocked.eq(false))
+            .filter(users::left_at.is_null())
+            .filter(leaderboard_cmp("<", (xp, user_id)))
+            .order((users::xp.desc(), users::user_id.desc()))
             .limit(n)
             .get_results(&self.pool.get().unwrap())
     }
 
+    /// Get the `n` users ranked just above `(xp, user_id)` on a guild's
+    /// leaderboard, highest first
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM users
+    /// WHERE guild_id = <guild_id> AND NOT blocked AND left_at IS NULL
+    ///     AND (xp, user_id) > (<xp>, <user_id>)
+    /// ORDER BY xp ASC, user_id ASC
+    /// LIMIT <n>;
+    /// ```
+    pub fn get_leaderboard_before(
+        &self,
+        guild_id: GuildId,
+        (xp, user_id): (i32, i64),
+        n: i64,
+    ) -> Result<Vec<User>, DieselError> {
+        let mut users: Vec<User> = users::table
+            .filter(users::guild_id.eq(guild_id.0 as i64))
+            .filter(users::blocked.eq(false))
+            .filter(users::left_at.is_null())
+            .filter(leaderboard_cmp(">", (xp, user_id)))
+            .order((users::xp.asc(), users::user_id.asc()))
+            .limit(n)
+            .get_results(&self.pool.get().unwrap())?;
+
+        users.reverse();
+
+        Ok(users)
+    }
+
     // -- guilds --
 
     pub fn create_guild(
@@ -209,13 +753,29 @@ impl Database {
             .get_result(&self.pool.get().unwrap())
     }
 
+    /// Get a guild from redis or postgres, creating a row with the default
+    /// settings if the guild hasn't been seen before
+    ///
+    /// # SQL:
+    /// ```sql
+    /// INSERT INTO guilds (guild_id, prefix)
+    /// VALUES (...)
+    /// ON CONFLICT DO NOTHING;
+    ///
+    /// SELECT * FROM guilds
+    /// WHERE guild_id = <guild_id>;
+    /// ```
     pub fn get_guild(&self, guild_id: GuildId) -> Result<Guild, DieselError> {
         if let Some(from_redis) = self.redis.get_guild(guild_id) {
             Ok(from_redis)
         } else {
+            let conn = self.pool.get().unwrap();
+
+            self.ensure_guild(&conn, guild_id)?;
+
             let guild = guilds::table
                 .filter(guilds::guild_id.eq(guild_id.0 as i64))
-                .get_result(&self.pool.get().unwrap())?;
+                .get_result(&conn)?;
 
             self.redis.set_guild(&guild);
 
@@ -263,4 +823,1719 @@ impl Database {
 
         Ok(saved)
     }
+
+    /// Set the range of XP a guild's members can earn per message
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET min_xp = <min_xp>, max_xp = <max_xp>
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_xp_range(
+        &self,
+        guild_id: GuildId,
+        min_xp: i32,
+        max_xp: i32,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set((guilds::min_xp.eq(min_xp), guilds::max_xp.eq(max_xp)))
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Set how long a guild's members have to wait between XP grants
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET xp_timeout_secs = <secs>
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_xp_timeout(
+        &self,
+        guild_id: GuildId,
+        secs: i32,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(guilds::xp_timeout_secs.eq(secs))
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Set the template used to announce level-ups. `None` goes back to the
+    /// default message
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET levelup_message = <message>
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_levelup_message(
+        &self,
+        guild_id: GuildId,
+        message: Option<String>,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(guilds::levelup_message.eq(message))
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Set where level-ups are announced
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET levelup_mode = <mode>, levelup_channel = <channel_id>
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_levelup_mode(
+        &self,
+        guild_id: GuildId,
+        mode: AnnounceMode,
+        channel_id: Option<ChannelId>,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set((
+            guilds::levelup_mode.eq(mode.as_str()),
+            guilds::levelup_channel.eq(channel_id.map(|c| c.0 as i64)),
+        ))
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Replace the channels and roles that do or don't earn XP in a guild
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET xp_ignored_channels = <...>, xp_ignored_roles = <...>,
+    ///     xp_allowed_channels = <...>, xp_whitelist_only = <...>
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_xp_filters(
+        &self,
+        guild_id: GuildId,
+        filters: &XpFilters,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(filters)
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Replace the rules for earning XP in a guild's voice channels
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET voice_xp_enabled = <...>, voice_xp_per_minute = <...>, ...
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_voice_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &VoiceSettings,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(settings)
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Set the curve that decides how much XP each level in a guild costs
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET level_curve = <curve>
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_level_curve(
+        &self,
+        guild_id: GuildId,
+        curve: &LevelCurve,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(guilds::level_curve.eq(curve.to_string()))
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Replace the rules for how inactive members lose XP in a guild
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET decay_enabled = <...>, decay_grace_days = <...>, ...
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_decay_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &DecaySettings,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(settings)
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Record the day decay was last applied in a guild
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET decay_last_run = <day>
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_decay_last_run(
+        &self,
+        guild_id: GuildId,
+        day: NaiveDate,
+    ) -> Result<Guild, DieselError> {
+        self.redis.del_guild(&guild_id);
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(guilds::decay_last_run.eq(day))
+        .get_result(&self.pool.get().unwrap())?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Get the guilds with decay turned on that haven't had it applied on
+    /// `today` yet
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM guilds
+    /// WHERE decay_enabled
+    ///     AND (decay_last_run IS NULL OR decay_last_run < <today>);
+    /// ```
+    pub fn get_guilds_due_decay(
+        &self,
+        today: NaiveDate,
+    ) -> Result<Vec<Guild>, DieselError> {
+        guilds::table
+            .filter(guilds::decay_enabled.eq(true))
+            .filter(
+                guilds::decay_last_run
+                    .is_null()
+                    .or(guilds::decay_last_run.lt(today)),
+            )
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Set what happens to members' XP when they leave a guild. Members who
+    /// were hidden are shown again if the guild goes back to keeping them
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET departed_mode = <mode>
+    /// WHERE guild_id = <guild_id>;
+    ///
+    /// UPDATE users
+    /// SET left_at = NULL
+    /// WHERE guild_id = <guild_id> AND left_at IS NOT NULL
+    /// RETURNING user_id;
+    /// ```
+    pub fn set_guild_departed_mode(
+        &self,
+        guild_id: GuildId,
+        mode: DepartedMode,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(guilds::departed_mode.eq(mode.as_str()))
+        .get_result(&conn)?;
+
+        if mode == DepartedMode::Keep {
+            let shown: Vec<i64> = diesel::update(
+                users::table
+                    .filter(users::guild_id.eq(guild_id.0 as i64))
+                    .filter(users::left_at.is_not_null()),
+            )
+            .set(users::left_at.eq(None::<DateTime<Utc>>))
+            .returning(users::user_id)
+            .get_results(&conn)?;
+
+            for user_id in shown {
+                self.redis.del_user(&guild_id, &UserId(user_id as u64));
+            }
+        }
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Record that the bot was removed from a guild, or that it's back. A
+    /// guild it's already been removed from keeps the time it was first
+    /// removed
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET left_at = <NOW() if left, otherwise NULL>
+    /// WHERE guild_id = <guild_id>
+    ///     AND left_at IS <NULL if left, otherwise NOT NULL>;
+    /// ```
+    pub fn set_guild_left(
+        &self,
+        guild_id: GuildId,
+        left: bool,
+    ) -> Result<(), DieselError> {
+        self.redis.del_guild(&guild_id);
+
+        let guild =
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64));
+
+        if left {
+            diesel::update(guild.filter(guilds::left_at.is_null()))
+                .set(guilds::left_at.eq(Utc::now()))
+                .execute(&self.pool.get().unwrap())?;
+        } else {
+            diesel::update(guild.filter(guilds::left_at.is_not_null()))
+                .set(guilds::left_at.eq(None::<DateTime<Utc>>))
+                .execute(&self.pool.get().unwrap())?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the guilds the bot was removed from before `cutoff`
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM guilds
+    /// WHERE left_at < <cutoff>;
+    /// ```
+    pub fn get_guilds_left_before(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<Guild>, DieselError> {
+        guilds::table
+            .filter(guilds::left_at.lt(cutoff))
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Set how a guild's XP multipliers are combined
+    ///
+    /// # SQL:
+    /// ```sql
+    /// UPDATE guilds
+    /// SET xp_multiplier_stack = <stack>
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn set_guild_multiplier_stack(
+        &self,
+        guild_id: GuildId,
+        stack: MultiplierStack,
+    ) -> Result<Guild, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        self.redis.del_guild(&guild_id);
+        self.ensure_guild(&conn, guild_id)?;
+
+        let saved = diesel::update(
+            guilds::table.filter(guilds::guild_id.eq(guild_id.0 as i64)),
+        )
+        .set(guilds::xp_multiplier_stack.eq(stack.as_str()))
+        .get_result(&conn)?;
+
+        self.redis.set_guild(&saved);
+
+        Ok(saved)
+    }
+
+    /// Insert a row with the default settings for a guild if it doesn't have
+    /// one yet
+    fn ensure_guild(
+        &self,
+        conn: &PgConnection,
+        guild_id: GuildId,
+    ) -> Result<(), DieselError> {
+        let new_guild = NewGuild {
+            guild_id: guild_id.0 as i64,
+            prefix: "~".to_string(),
+        };
+
+        diesel::insert_into(guilds::table)
+            .values(&new_guild)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    // -- level rewards --
+
+    /// Give a role to members once they reach `level`, replacing the level
+    /// the role was previously given at
+    ///
+    /// # SQL:
+    /// ```sql
+    /// INSERT INTO level_rewards (guild_id, role_id, level)
+    /// VALUES (...)
+    /// ON CONFLICT (guild_id, role_id)
+    /// DO
+    ///     UPDATE SET level = <level>;
+    /// ```
+    pub fn add_level_reward(
+        &self,
+        guild_id: GuildId,
+        role_id: RoleId,
+        level: i32,
+    ) -> Result<LevelReward, DieselError> {
+        let new_reward = NewLevelReward {
+            guild_id: guild_id.0 as i64,
+            role_id: role_id.0 as i64,
+            level,
+        };
+
+        diesel::insert_into(level_rewards::table)
+            .values(&new_reward)
+            .on_conflict((level_rewards::guild_id, level_rewards::role_id))
+            .do_update()
+            .set(level_rewards::level.eq(level))
+            .get_result(&self.pool.get().unwrap())
+    }
+
+    /// Stop giving a role out as a level reward. Returns the number of rows
+    /// removed
+    ///
+    /// # SQL:
+    /// ```sql
+    /// DELETE FROM level_rewards
+    /// WHERE guild_id = <guild_id> AND role_id = <role_id>;
+    /// ```
+    pub fn remove_level_reward(
+        &self,
+        guild_id: GuildId,
+        role_id: RoleId,
+    ) -> Result<usize, DieselError> {
+        diesel::delete(
+            level_rewards::table
+                .filter(level_rewards::guild_id.eq(guild_id.0 as i64))
+                .filter(level_rewards::role_id.eq(role_id.0 as i64)),
+        )
+        .execute(&self.pool.get().unwrap())
+    }
+
+    /// Get all of a guild's level rewards, lowest level first
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM level_rewards
+    /// WHERE guild_id = <guild_id>
+    /// ORDER BY level;
+    /// ```
+    pub fn get_level_rewards(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<LevelReward>, DieselError> {
+        level_rewards::table
+            .filter(level_rewards::guild_id.eq(guild_id.0 as i64))
+            .order(level_rewards::level.asc())
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    // -- multipliers --
+
+    /// Set the XP multiplier for a role or channel
+    ///
+    /// # SQL:
+    /// ```sql
+    /// INSERT INTO xp_multipliers (guild_id, target_id, kind, multiplier)
+    /// VALUES (...)
+    /// ON CONFLICT (guild_id, target_id)
+    /// DO
+    ///     UPDATE SET kind = <kind>, multiplier = <multiplier>;
+    /// ```
+    pub fn set_xp_multiplier(
+        &self,
+        guild_id: GuildId,
+        target: MultiplierTarget,
+        target_id: u64,
+        multiplier: f32,
+    ) -> Result<XpMultiplier, DieselError> {
+        self.redis.del_multipliers(&guild_id);
+
+        let new_multiplier = NewXpMultiplier {
+            guild_id: guild_id.0 as i64,
+            target_id: target_id as i64,
+            kind: target.as_str().to_string(),
+            multiplier,
+        };
+
+        diesel::insert_into(xp_multipliers::table)
+            .values(&new_multiplier)
+            .on_conflict((xp_multipliers::guild_id, xp_multipliers::target_id))
+            .do_update()
+            .set((
+                xp_multipliers::kind.eq(target.as_str()),
+                xp_multipliers::multiplier.eq(multiplier),
+            ))
+            .get_result(&self.pool.get().unwrap())
+    }
+
+    /// Remove the XP multiplier for a role or channel. Returns the number of
+    /// rows removed
+    ///
+    /// # SQL:
+    /// ```sql
+    /// DELETE FROM xp_multipliers
+    /// WHERE guild_id = <guild_id> AND target_id = <target_id>;
+    /// ```
+    pub fn remove_xp_multiplier(
+        &self,
+        guild_id: GuildId,
+        target_id: u64,
+    ) -> Result<usize, DieselError> {
+        self.redis.del_multipliers(&guild_id);
+
+        diesel::delete(
+            xp_multipliers::table
+                .filter(xp_multipliers::guild_id.eq(guild_id.0 as i64))
+                .filter(xp_multipliers::target_id.eq(target_id as i64)),
+        )
+        .execute(&self.pool.get().unwrap())
+    }
+
+    /// Get all of a guild's XP multipliers from redis or postgres
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM xp_multipliers
+    /// WHERE guild_id = <guild_id>;
+    /// ```
+    pub fn get_xp_multipliers(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<XpMultiplier>, DieselError> {
+        if let Some(from_redis) = self.redis.get_multipliers(&guild_id) {
+            Ok(from_redis)
+        } else {
+            let multipliers = xp_multipliers::table
+                .filter(xp_multipliers::guild_id.eq(guild_id.0 as i64))
+                .order(xp_multipliers::multiplier.desc())
+                .get_results(&self.pool.get().unwrap())?;
+
+            self.redis.set_multipliers(&guild_id, &multipliers);
+
+            Ok(multipliers)
+        }
+    }
+
+    /// Take up to `days` of the guild's decay from up to `n` of its users who
+    /// haven't earned XP since `cutoff`, starting after the user with the row
+    /// ID `after_id`. Each user is only charged for the whole days since they
+    /// passed `cutoff`, so catching up doesn't take more than they owe. Nobody
+    /// is taken below `floor`, and every change is recorded in the ledger.
+    /// Returns the updated users in row ID order
+    ///
+    /// # SQL:
+    /// ```sql
+    /// WITH batch AS (
+    ///     SELECT id, xp, LEAST(
+    ///         <days>, FLOOR(EXTRACT(EPOCH FROM <cutoff> - last_xp_at) / 86400)
+    ///     ) AS days FROM users
+    ///     WHERE guild_id = <guild_id> AND id > <after_id> AND NOT blocked
+    ///         AND last_xp_at <= <cutoff> - INTERVAL '1 day' AND xp > <floor>
+    ///     ORDER BY id
+    ///     LIMIT <n>
+    ///     FOR UPDATE
+    /// ), u AS (
+    ///     UPDATE users
+    ///     SET xp = GREATEST(<floor>, xp - <decay over batch.days>)
+    ///     FROM batch
+    ///     WHERE users.id = batch.id
+    ///     RETURNING users.*
+    /// ), e AS (
+    ///     INSERT INTO xp_events (guild_id, user_id, xp, source)
+    ///     SELECT u.guild_id, u.user_id, u.xp - batch.xp, 'decay'
+    ///     FROM u JOIN batch ON batch.id = u.id
+    /// )
+    /// SELECT * FROM u ORDER BY id;
+    /// ```
+    pub fn decay_guild_users(
+        &self,
+        guild: &Guild,
+        cutoff: DateTime<Utc>,
+        floor: i32,
+        days: i32,
+        after_id: i32,
+        n: i64,
+    ) -> Result<Vec<User>, DieselError> {
+        let guild_id = GuildId(guild.guild_id as u64);
+
+        let users: Vec<User> = diesel::sql_query(
+            "WITH batch AS ( \
+                 SELECT id, xp, LEAST($8, FLOOR( \
+                     EXTRACT(EPOCH FROM $3 - last_xp_at) / 86400 \
+                 ))::INTEGER AS days FROM users \
+                 WHERE guild_id = $1 AND id > $2 AND NOT blocked \
+                     AND last_xp_at <= $3 - INTERVAL '1 day' AND xp > $4 \
+                 ORDER BY id \
+                 LIMIT $5 \
+                 FOR UPDATE \
+             ), u AS ( \
+                 UPDATE users \
+                 SET xp = GREATEST($4, users.xp - CASE \
+                     WHEN $6 = 'percent' THEN CEIL( \
+                         users.xp * (1 - POWER(1 - $7 / 100.0, batch.days)) \
+                     )::INTEGER \
+                     ELSE $7 * batch.days \
+                 END) \
+                 FROM batch \
+                 WHERE users.id = batch.id \
+                 RETURNING users.* \
+             ), e AS ( \
+                 INSERT INTO xp_events (guild_id, user_id, xp, source) \
+                 SELECT u.guild_id, u.user_id, u.xp - batch.xp, $9 \
+                 FROM u JOIN batch ON batch.id = u.id \
+             ) \
+             SELECT * FROM u ORDER BY id",
+        )
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .bind::<Integer, _>(after_id)
+        .bind::<Timestamptz, _>(cutoff)
+        .bind::<Integer, _>(floor)
+        .bind::<BigInt, _>(n)
+        .bind::<Text, _>(guild.decay_mode().as_str())
+        .bind::<Integer, _>(guild.decay_amount)
+        .bind::<Integer, _>(days)
+        .bind::<Text, _>(XpSource::Decay.as_str())
+        .get_results(&self.pool.get().unwrap())?;
+
+        for user in &users {
+            self.redis.del_user(&guild_id, &UserId(user.user_id as u64));
+        }
+
+        Ok(users)
+    }
+
+    // -- xp history --
+
+    /// Get a user's `n` most recent XP changes in a guild, newest first
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM xp_events
+    /// WHERE guild_id = <guild_id> AND user_id = <user_id>
+    /// ORDER BY created_at DESC, id DESC
+    /// LIMIT <n>;
+    /// ```
+    pub fn get_xp_history(
+        &self,
+        user_id: UserId,
+        guild_id: GuildId,
+        n: i64,
+    ) -> Result<Vec<XpEvent>, DieselError> {
+        xp_events::table
+            .filter(xp_events::guild_id.eq(guild_id.0 as i64))
+            .filter(xp_events::user_id.eq(user_id.0 as i64))
+            .order((xp_events::created_at.desc(), xp_events::id.desc()))
+            .limit(n)
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Count the users who earned XP in a guild between two days (UTC,
+    /// inclusive)
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT COUNT(DISTINCT user_id) FROM xp_daily
+    /// WHERE guild_id = <guild_id> AND day BETWEEN <from> AND <to>
+    ///     AND user_id NOT IN (<blocked or hidden users>);
+    /// ```
+    pub fn count_window_users(
+        &self,
+        guild_id: GuildId,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<i64, DieselError> {
+        let count: Count = diesel::sql_query(
+            "SELECT COUNT(DISTINCT user_id) AS count FROM xp_daily \
+             WHERE guild_id = $1 AND day BETWEEN $2 AND $3 \
+             AND user_id NOT IN ( \
+                 SELECT user_id FROM users \
+                 WHERE guild_id = $1 AND (blocked OR left_at IS NOT NULL) \
+             )",
+        )
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .bind::<Date, _>(from)
+        .bind::<Date, _>(to)
+        .get_result(&self.pool.get().unwrap())?;
+
+        Ok(count.count)
+    }
+
+    /// Get `n` users from a guild's leaderboard for the XP earned between two
+    /// days (UTC, inclusive), skipping the first `offset`
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT user_id, SUM(xp) AS xp FROM xp_daily
+    /// WHERE guild_id = <guild_id> AND day BETWEEN <from> AND <to>
+    ///     AND user_id NOT IN (<blocked or hidden users>)
+    /// GROUP BY user_id
+    /// ORDER BY xp DESC, user_id DESC
+    /// LIMIT <n> OFFSET <offset>;
+    /// ```
+    pub fn get_window_leaderboard(
+        &self,
+        guild_id: GuildId,
+        from: NaiveDate,
+        to: NaiveDate,
+        offset: i64,
+        n: i64,
+    ) -> Result<Vec<WindowUser>, DieselError> {
+        diesel::sql_query(
+            "SELECT user_id, SUM(xp)::BIGINT AS xp FROM xp_daily \
+             WHERE guild_id = $1 AND day BETWEEN $2 AND $3 \
+             AND user_id NOT IN ( \
+                 SELECT user_id FROM users \
+                 WHERE guild_id = $1 AND (blocked OR left_at IS NOT NULL) \
+             ) \
+             GROUP BY user_id \
+             ORDER BY xp DESC, user_id DESC \
+             LIMIT $4 OFFSET $5",
+        )
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .bind::<Date, _>(from)
+        .bind::<Date, _>(to)
+        .bind::<BigInt, _>(n)
+        .bind::<BigInt, _>(offset)
+        .get_results(&self.pool.get().unwrap())
+    }
+
+    // -- seasons --
+
+    /// End a guild's current season: archive the leaderboard as it stands,
+    /// then cut everyone's XP down to `keep_percent`% of what it was. The
+    /// changes are recorded in the ledger
+    ///
+    /// # SQL:
+    /// ```sql
+    /// BEGIN;
+    ///
+    /// INSERT INTO seasons (guild_id, number, started_at)
+    /// VALUES (<guild_id>, <last number + 1>, <last ended_at>);
+    ///
+    /// INSERT INTO season_standings (season_id, position, user_id, xp)
+    /// SELECT <season_id>, ROW_NUMBER() OVER (...), user_id, xp FROM users
+    /// WHERE guild_id = <guild_id> AND NOT blocked AND left_at IS NULL
+    ///     AND xp > 0;
+    ///
+    /// -- see Database::scale_guild_xp
+    ///
+    /// COMMIT;
+    /// ```
+    pub fn end_season(
+        &self,
+        guild_id: GuildId,
+        keep_percent: i32,
+    ) -> Result<Season, DieselError> {
+        let conn = self.pool.get().unwrap();
+
+        let (season, reset) = conn.transaction::<_, DieselError, _>(|| {
+            let last: Option<Season> = seasons::table
+                .filter(seasons::guild_id.eq(guild_id.0 as i64))
+                .order(seasons::number.desc())
+                .first(&conn)
+                .optional()?;
+
+            let new_season = NewSeason {
+                guild_id: guild_id.0 as i64,
+                number: last.as_ref().map_or(1, |s| s.number + 1),
+                started_at: last.map(|s| s.ended_at),
+            };
+
+            let season: Season = diesel::insert_into(seasons::table)
+                .values(&new_season)
+                .get_result(&conn)?;
+
+            diesel::sql_query(
+                "INSERT INTO season_standings \
+                     (season_id, position, user_id, xp) \
+                 SELECT $1, \
+                     ROW_NUMBER() OVER (ORDER BY xp DESC, user_id DESC), \
+                     user_id, xp \
+                 FROM users \
+                 WHERE guild_id = $2 AND NOT blocked AND left_at IS NULL \
+                     AND xp > 0",
+            )
+            .bind::<Integer, _>(season.id)
+            .bind::<BigInt, _>(guild_id.0 as i64)
+            .execute(&conn)?;
+
+            let reset = Self::scale_guild_xp(
+                &conn,
+                guild_id,
+                keep_percent,
+                XpSource::Season,
+                None,
+            )?;
+
+            Ok((season, reset))
+        })?;
+
+        for user in &reset {
+            self.redis.del_user(&guild_id, &UserId(user.user_id as u64));
+        }
+
+        Ok(season)
+    }
+
+    /// Reset the XP of every user in a guild to 0, recording the changes in
+    /// the ledger as made by `actor`. Returns the users that changed
+    ///
+    /// # SQL:
+    /// see Database::scale_guild_xp
+    pub fn reset_guild_xp(
+        &self,
+        guild_id: GuildId,
+        actor: UserId,
+    ) -> Result<Vec<User>, DieselError> {
+        let reset = Self::scale_guild_xp(
+            &self.pool.get().unwrap(),
+            guild_id,
+            0,
+            XpSource::Admin,
+            Some(actor),
+        )?;
+
+        for user in &reset {
+            self.redis.del_user(&guild_id, &UserId(user.user_id as u64));
+        }
+
+        Ok(reset)
+    }
+
+    /// Cut the XP of every user in a guild down to `percent`% of what it was,
+    /// recording the changes in the ledger. Returns the users that changed.
+    /// Takes a connection so it can run inside a transaction, and leaves
+    /// invalidating the cache to the caller
+    ///
+    /// # SQL:
+    /// ```sql
+    /// WITH old AS (
+    ///     SELECT id, xp FROM users
+    ///     WHERE guild_id = <guild_id> AND xp > 0
+    ///     FOR UPDATE
+    /// ), u AS (
+    ///     UPDATE users
+    ///     SET xp = xp * <percent> / 100
+    ///     FROM old
+    ///     WHERE users.id = old.id
+    ///     RETURNING users.*
+    /// ), e AS (
+    ///     INSERT INTO xp_events (guild_id, user_id, xp, source, actor_id)
+    ///     SELECT u.guild_id, u.user_id, u.xp - old.xp, <source>, <actor>
+    ///     FROM u JOIN old ON old.id = u.id
+    ///     WHERE u.xp <> old.xp
+    /// )
+    /// SELECT * FROM u;
+    /// ```
+    fn scale_guild_xp(
+        conn: &PgConnection,
+        guild_id: GuildId,
+        percent: i32,
+        source: XpSource,
+        actor: Option<UserId>,
+    ) -> Result<Vec<User>, DieselError> {
+        diesel::sql_query(
+            "WITH old AS ( \
+                 SELECT id, xp FROM users \
+                 WHERE guild_id = $1 AND xp > 0 \
+                 FOR UPDATE \
+             ), u AS ( \
+                 UPDATE users \
+                 SET xp = (users.xp::BIGINT * $2 / 100)::INTEGER \
+                 FROM old \
+                 WHERE users.id = old.id \
+                 RETURNING users.* \
+             ), e AS ( \
+                 INSERT INTO xp_events \
+                     (guild_id, user_id, xp, source, actor_id) \
+                 SELECT u.guild_id, u.user_id, u.xp - old.xp, $3, $4 \
+                 FROM u JOIN old ON old.id = u.id \
+                 WHERE u.xp <> old.xp \
+             ) \
+             SELECT * FROM u",
+        )
+        .bind::<BigInt, _>(guild_id.0 as i64)
+        .bind::<Integer, _>(percent)
+        .bind::<Text, _>(source.as_str())
+        .bind::<Nullable<BigInt>, _>(actor.map(|a| a.0 as i64))
+        .get_results(conn)
+    }
+
+    /// Get a guild's finished seasons, newest first
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM seasons
+    /// WHERE guild_id = <guild_id>
+    /// ORDER BY number DESC;
+    /// ```
+    pub fn get_seasons(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<Season>, DieselError> {
+        seasons::table
+            .filter(seasons::guild_id.eq(guild_id.0 as i64))
+            .order(seasons::number.desc())
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Get one of a guild's finished seasons by its number
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM seasons
+    /// WHERE guild_id = <guild_id> AND number = <number>;
+    /// ```
+    pub fn get_season(
+        &self,
+        guild_id: GuildId,
+        number: i32,
+    ) -> Result<Option<Season>, DieselError> {
+        seasons::table
+            .filter(seasons::guild_id.eq(guild_id.0 as i64))
+            .filter(seasons::number.eq(number))
+            .get_result(&self.pool.get().unwrap())
+            .optional()
+    }
+
+    /// Count the members on a season's final leaderboard
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT COUNT(*) FROM season_standings
+    /// WHERE season_id = <season_id>;
+    /// ```
+    pub fn count_season_standings(
+        &self,
+        season_id: i32,
+    ) -> Result<i64, DieselError> {
+        season_standings::table
+            .filter(season_standings::season_id.eq(season_id))
+            .count()
+            .get_result(&self.pool.get().unwrap())
+    }
+
+    /// Get `n` places from a season's final leaderboard, skipping the first
+    /// `offset`
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM season_standings
+    /// WHERE season_id = <season_id>
+    /// ORDER BY position
+    /// LIMIT <n> OFFSET <offset>;
+    /// ```
+    pub fn get_season_standings(
+        &self,
+        season_id: i32,
+        offset: i64,
+        n: i64,
+    ) -> Result<Vec<SeasonStanding>, DieselError> {
+        season_standings::table
+            .filter(season_standings::season_id.eq(season_id))
+            .order(season_standings::position)
+            .offset(offset)
+            .limit(n)
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    // -- backups --
+
+    /// Read everything stored for a guild into a backup. It's all read in
+    /// one transaction, so the tables match each other
+    ///
+    /// # SQL:
+    /// ```sql
+    /// BEGIN ISOLATION LEVEL REPEATABLE READ, READ ONLY;
+    ///
+    /// SELECT * FROM guilds WHERE guild_id = <guild_id>;
+    /// SELECT * FROM users WHERE guild_id = <guild_id>;
+    /// SELECT * FROM level_rewards WHERE guild_id = <guild_id>;
+    /// SELECT * FROM xp_multipliers WHERE guild_id = <guild_id>;
+    /// SELECT * FROM seasons WHERE guild_id = <guild_id>;
+    ///
+    /// SELECT season_standings.* FROM season_standings
+    /// INNER JOIN seasons ON seasons.id = season_standings.season_id
+    /// WHERE seasons.guild_id = <guild_id>;
+    ///
+    /// COMMIT;
+    /// ```
+    pub fn backup_guild(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<GuildBackup, DieselError> {
+        let conn = self.pool.get().unwrap();
+        let gid = guild_id.0 as i64;
+
+        self.ensure_guild(&conn, guild_id)?;
+
+        conn.build_transaction()
+            .repeatable_read()
+            .read_only()
+            .run(|| {
+                Ok(GuildBackup {
+                    version: BACKUP_VERSION,
+                    created_at: Utc::now(),
+                    guild: guilds::table
+                        .filter(guilds::guild_id.eq(gid))
+                        .get_result(&conn)?,
+                    users: users::table
+                        .filter(users::guild_id.eq(gid))
+                        .order(users::id)
+                        .get_results(&conn)?,
+                    level_rewards: level_rewards::table
+                        .filter(level_rewards::guild_id.eq(gid))
+                        .order(level_rewards::level)
+                        .get_results(&conn)?,
+                    xp_multipliers: xp_multipliers::table
+                        .filter(xp_multipliers::guild_id.eq(gid))
+                        .order(xp_multipliers::id)
+                        .get_results(&conn)?,
+                    seasons: seasons::table
+                        .filter(seasons::guild_id.eq(gid))
+                        .order(seasons::number)
+                        .get_results(&conn)?,
+                    season_standings: season_standings::table
+                        .inner_join(seasons::table)
+                        .filter(seasons::guild_id.eq(gid))
+                        .select(season_standings::all_columns)
+                        .order((
+                            season_standings::season_id,
+                            season_standings::position,
+                        ))
+                        .get_results(&conn)?,
+                })
+            })
+    }
+
+    /// Replace everything stored for a guild with a backup, which can come
+    /// from another guild, in which case the parts that point at its
+    /// channels and roles are left out. It's done in one transaction, so if
+    /// anything in the backup can't be restored nothing changes. The
+    /// difference in each member's XP is recorded in the ledger as made by
+    /// `actor`
+    ///
+    /// # SQL:
+    /// ```sql
+    /// BEGIN;
+    ///
+    /// UPDATE guilds SET ... WHERE guild_id = <guild_id>;
+    ///
+    /// WITH backup AS (
+    ///     SELECT user_id, xp FROM json_populate_recordset(NULL::users, ...)
+    /// ), old AS (
+    ///     SELECT user_id, xp FROM users
+    ///     WHERE guild_id = <guild_id>
+    ///     FOR UPDATE
+    /// )
+    /// INSERT INTO xp_events (guild_id, user_id, xp, source, actor_id)
+    /// SELECT <guild_id>, user_id, <backup xp - old xp>, 'restore', <actor>
+    /// FROM backup FULL JOIN old USING (user_id)
+    /// WHERE <backup xp> <> <old xp>;
+    ///
+    /// DELETE FROM users WHERE guild_id = <guild_id> RETURNING user_id;
+    /// INSERT INTO users (...) SELECT ... FROM json_populate_recordset(...);
+    ///
+    /// -- the same for level_rewards, xp_multipliers and seasons, with each
+    /// -- season's standings inserted under its new row ID
+    ///
+    /// COMMIT;
+    /// ```
+    pub fn restore_guild(
+        &self,
+        guild_id: GuildId,
+        mut backup: GuildBackup,
+        actor: UserId,
+    ) -> Result<(), DieselError> {
+        let conn = self.pool.get().unwrap();
+        let gid = guild_id.0 as i64;
+
+        if backup.guild.guild_id != gid {
+            backup.drop_guild_ids();
+        }
+
+        let mut guild = backup.guild;
+        guild.guild_id = gid;
+        // the bot is in the guild it's restoring to, wherever it was backed
+        // up from
+        guild.left_at = None;
+
+        // users and standings can run to thousands of rows, more than fit in
+        // a statement's parameters, so they're sent as JSON
+        let users_json = serde_json::to_string(&backup.users).unwrap();
+        let seasons_json = serde_json::to_string(&backup.seasons).unwrap();
+        let standings_json =
+            serde_json::to_string(&backup.season_standings).unwrap();
+
+        let rewards = backup
+            .level_rewards
+            .iter()
+            .map(|r| NewLevelReward {
+                guild_id: gid,
+                role_id: r.role_id,
+                level: r.level,
+            })
+            .collect::<Vec<NewLevelReward>>();
+        let multipliers = backup
+            .xp_multipliers
+            .into_iter()
+            .map(|m| NewXpMultiplier {
+                guild_id: gid,
+                target_id: m.target_id,
+                kind: m.kind,
+                multiplier: m.multiplier,
+            })
+            .collect::<Vec<NewXpMultiplier>>();
+
+        let old_users = conn.transaction::<_, DieselError, _>(|| {
+            self.ensure_guild(&conn, guild_id)?;
+
+            diesel::update(guilds::table.filter(guilds::guild_id.eq(gid)))
+                .set(&guild)
+                .execute(&conn)?;
+
+            diesel::sql_query(
+                "WITH backup AS ( \
+                     SELECT user_id, xp \
+                     FROM json_populate_recordset(NULL::users, $2::JSON) \
+                 ), old AS ( \
+                     SELECT user_id, xp FROM users \
+                     WHERE guild_id = $1 \
+                     FOR UPDATE \
+                 ) \
+                 INSERT INTO xp_events \
+                     (guild_id, user_id, xp, source, actor_id) \
+                 SELECT $1, user_id, \
+                     COALESCE(backup.xp, 0) - COALESCE(old.xp, 0), $3, $4 \
+                 FROM backup FULL JOIN old USING (user_id) \
+                 WHERE COALESCE(backup.xp, 0) <> COALESCE(old.xp, 0)",
+            )
+            .bind::<BigInt, _>(gid)
+            .bind::<Text, _>(&users_json)
+            .bind::<Text, _>(XpSource::Restore.as_str())
+            .bind::<BigInt, _>(actor.0 as i64)
+            .execute(&conn)?;
+
+            let old_users: Vec<i64> =
+                diesel::delete(users::table.filter(users::guild_id.eq(gid)))
+                    .returning(users::user_id)
+                    .get_results(&conn)?;
+
+            diesel::sql_query(
+                "INSERT INTO users \
+                     (user_id, guild_id, xp, blocked, last_xp_at, left_at) \
+                 SELECT user_id, $1, xp, blocked, last_xp_at, left_at \
+                 FROM json_populate_recordset(NULL::users, $2::JSON)",
+            )
+            .bind::<BigInt, _>(gid)
+            .bind::<Text, _>(&users_json)
+            .execute(&conn)?;
+
+            diesel::delete(
+                level_rewards::table.filter(level_rewards::guild_id.eq(gid)),
+            )
+            .execute(&conn)?;
+            diesel::insert_into(level_rewards::table)
+                .values(&rewards)
+                .execute(&conn)?;
+
+            diesel::delete(
+                xp_multipliers::table.filter(xp_multipliers::guild_id.eq(gid)),
+            )
+            .execute(&conn)?;
+            diesel::insert_into(xp_multipliers::table)
+                .values(&multipliers)
+                .execute(&conn)?;
+
+            // standings go with them, as they cascade
+            diesel::delete(seasons::table.filter(seasons::guild_id.eq(gid)))
+                .execute(&conn)?;
+
+            diesel::sql_query(
+                "WITH backup AS ( \
+                     SELECT * \
+                     FROM json_populate_recordset(NULL::seasons, $2::JSON) \
+                 ), s AS ( \
+                     INSERT INTO seasons \
+                         (guild_id, number, started_at, ended_at) \
+                     SELECT $1, number, started_at, ended_at FROM backup \
+                     RETURNING id, number \
+                 ) \
+                 INSERT INTO season_standings \
+                     (season_id, position, user_id, xp) \
+                 SELECT s.id, st.position, st.user_id, st.xp \
+                 FROM json_populate_recordset( \
+                     NULL::season_standings, $3::JSON \
+                 ) AS st \
+                 JOIN backup ON backup.id = st.season_id \
+                 JOIN s ON s.number = backup.number",
+            )
+            .bind::<BigInt, _>(gid)
+            .bind::<Text, _>(&seasons_json)
+            .bind::<Text, _>(&standings_json)
+            .execute(&conn)?;
+
+            Ok(old_users)
+        })?;
+
+        self.redis.del_guild(&guild_id);
+        self.redis.del_multipliers(&guild_id);
+
+        for user_id in old_users {
+            self.redis.del_user(&guild_id, &UserId(user_id as u64));
+        }
+
+        Ok(())
+    }
+
+    // -- global --
+
+    /// Count the users who show up on the global leaderboard
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT COUNT(DISTINCT user_id) FROM users
+    /// WHERE NOT blocked AND left_at IS NULL
+    ///     AND guild_id NOT IN (
+    ///         SELECT guild_id FROM guilds WHERE left_at IS NOT NULL
+    ///     );
+    /// ```
+    pub fn count_global_users(&self) -> Result<i64, DieselError> {
+        users::table
+            .filter(users::blocked.eq(false))
+            .filter(users::left_at.is_null())
+            .filter(
+                users::guild_id.ne_all(
+                    guilds::table
+                        .filter(guilds::left_at.is_not_null())
+                        .select(guilds::guild_id),
+                ),
+            )
+            .select(sql::<BigInt>("COUNT(DISTINCT user_id)"))
+            .get_result(&self.pool.get().unwrap())
+    }
+
+    /// Get `n` users from the global leaderboard, skipping the first
+    /// `offset`
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT user_id, SUM(xp) AS xp, COUNT(*) AS guilds FROM users
+    /// WHERE NOT blocked AND left_at IS NULL
+    ///     AND guild_id NOT IN (
+    ///         SELECT guild_id FROM guilds WHERE left_at IS NOT NULL
+    ///     )
+    /// GROUP BY user_id
+    /// ORDER BY xp DESC, user_id DESC
+    /// LIMIT <n> OFFSET <offset>;
+    /// ```
+    pub fn get_global_leaderboard(
+        &self,
+        offset: i64,
+        n: i64,
+    ) -> Result<Vec<GlobalUser>, DieselError> {
+        diesel::sql_query(
+            "SELECT user_id, SUM(xp)::BIGINT AS xp, COUNT(*) AS guilds \
+             FROM users \
+             WHERE NOT blocked AND left_at IS NULL \
+                 AND guild_id NOT IN ( \
+                     SELECT guild_id FROM guilds WHERE left_at IS NOT NULL \
+                 ) \
+             GROUP BY user_id \
+             ORDER BY xp DESC, user_id DESC \
+             LIMIT $1 OFFSET $2",
+        )
+        .bind::<BigInt, _>(n)
+        .bind::<BigInt, _>(offset)
+        .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Get a user's XP summed across every guild, or `None` if they haven't
+    /// earned any
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT user_id, SUM(xp) AS xp, COUNT(*) AS guilds FROM users
+    /// WHERE user_id = <user_id> AND NOT blocked AND left_at IS NULL
+    ///     AND guild_id NOT IN (
+    ///         SELECT guild_id FROM guilds WHERE left_at IS NOT NULL
+    ///     )
+    /// GROUP BY user_id;
+    /// ```
+    pub fn get_global_user(
+        &self,
+        user_id: UserId,
+    ) -> Result<Option<GlobalUser>, DieselError> {
+        diesel::sql_query(
+            "SELECT user_id, SUM(xp)::BIGINT AS xp, COUNT(*) AS guilds \
+             FROM users \
+             WHERE user_id = $1 AND NOT blocked AND left_at IS NULL \
+                 AND guild_id NOT IN ( \
+                     SELECT guild_id FROM guilds WHERE left_at IS NOT NULL \
+                 ) \
+             GROUP BY user_id",
+        )
+        .bind::<BigInt, _>(user_id.0 as i64)
+        .get_result(&self.pool.get().unwrap())
+        .optional()
+    }
+
+    /// Get a user's position on the global leaderboard, starting at 1
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT COUNT(*) + 1 FROM (
+    ///     SELECT user_id FROM users
+    ///     WHERE NOT blocked AND left_at IS NULL
+    ///         AND guild_id NOT IN (
+    ///             SELECT guild_id FROM guilds WHERE left_at IS NOT NULL
+    ///         )
+    ///     GROUP BY user_id
+    ///     HAVING (SUM(xp), user_id) > (<xp>, <user_id>)
+    /// ) AS above;
+    /// ```
+    pub fn get_global_position(
+        &self,
+        user: &GlobalUser,
+    ) -> Result<i64, DieselError> {
+        let position: Count = diesel::sql_query(
+            "SELECT COUNT(*) + 1 AS count FROM ( \
+                 SELECT user_id FROM users \
+                 WHERE NOT blocked AND left_at IS NULL \
+                     AND guild_id NOT IN ( \
+                         SELECT guild_id FROM guilds WHERE left_at IS NOT NULL \
+                     ) \
+                 GROUP BY user_id \
+                 HAVING (SUM(xp), user_id) > ($1, $2) \
+             ) AS above",
+        )
+        .bind::<BigInt, _>(user.xp)
+        .bind::<BigInt, _>(user.user_id)
+        .get_result(&self.pool.get().unwrap())?;
+
+        Ok(position.count)
+    }
+
+    /// Get every guild a user has XP in, most XP first
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM users
+    /// WHERE user_id = <user_id> AND NOT blocked AND left_at IS NULL
+    ///     AND guild_id NOT IN (
+    ///         SELECT guild_id FROM guilds WHERE left_at IS NOT NULL
+    ///     )
+    /// ORDER BY xp DESC;
+    /// ```
+    pub fn get_user_guilds(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<User>, DieselError> {
+        users::table
+            .filter(users::user_id.eq(user_id.0 as i64))
+            .filter(users::blocked.eq(false))
+            .filter(users::left_at.is_null())
+            .filter(
+                users::guild_id.ne_all(
+                    guilds::table
+                        .filter(guilds::left_at.is_not_null())
+                        .select(guilds::guild_id),
+                ),
+            )
+            .order(users::xp.desc())
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    // -- user settings --
+
+    /// Get a user's settings, or the defaults if they haven't changed any
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM user_settings
+    /// WHERE user_id = <user_id>;
+    /// ```
+    pub fn get_user_settings(
+        &self,
+        user_id: UserId,
+    ) -> Result<UserSettings, DieselError> {
+        let settings = user_settings::table
+            .find(user_id.0 as i64)
+            .get_result(&self.pool.get().unwrap())
+            .optional()?;
+
+        Ok(settings.unwrap_or(UserSettings {
+            user_id: user_id.0 as i64,
+            ..Default::default()
+        }))
+    }
+
+    /// Set whether a user's global profile lists their guilds
+    ///
+    /// # SQL:
+    /// ```sql
+    /// INSERT INTO user_settings (user_id, hide_guilds)
+    /// VALUES (...)
+    /// ON CONFLICT (user_id)
+    /// DO
+    ///     UPDATE SET hide_guilds = <hide_guilds>;
+    /// ```
+    pub fn set_user_hide_guilds(
+        &self,
+        user_id: UserId,
+        hide_guilds: bool,
+    ) -> Result<UserSettings, DieselError> {
+        let settings = UserSettings {
+            user_id: user_id.0 as i64,
+            hide_guilds,
+        };
+
+        diesel::insert_into(user_settings::table)
+            .values(&settings)
+            .on_conflict(user_settings::user_id)
+            .do_update()
+            .set(user_settings::hide_guilds.eq(hide_guilds))
+            .get_result(&self.pool.get().unwrap())
+    }
+
+    // -- user data --
+
+    /// Get a user's rows in every guild, blocked or not
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM users
+    /// WHERE user_id = <user_id>
+    /// ORDER BY guild_id;
+    /// ```
+    pub fn get_user_rows(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<User>, DieselError> {
+        users::table
+            .filter(users::user_id.eq(user_id.0 as i64))
+            .order(users::guild_id)
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Get every change to a user's XP in any guild, oldest first
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM xp_events
+    /// WHERE user_id = <user_id>
+    /// ORDER BY id;
+    /// ```
+    pub fn get_user_xp_events(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<XpEvent>, DieselError> {
+        xp_events::table
+            .filter(xp_events::user_id.eq(user_id.0 as i64))
+            .order(xp_events::id)
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Get a user's daily XP totals in every guild
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM xp_daily
+    /// WHERE user_id = <user_id>
+    /// ORDER BY guild_id, day;
+    /// ```
+    pub fn get_user_xp_days(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<XpDay>, DieselError> {
+        xp_daily::table
+            .filter(xp_daily::user_id.eq(user_id.0 as i64))
+            .order((xp_daily::guild_id, xp_daily::day))
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Get a user's places in every guild's past seasons
+    ///
+    /// # SQL:
+    /// ```sql
+    /// SELECT * FROM season_standings
+    /// WHERE user_id = <user_id>
+    /// ORDER BY season_id;
+    /// ```
+    pub fn get_user_season_standings(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<SeasonStanding>, DieselError> {
+        season_standings::table
+            .filter(season_standings::user_id.eq(user_id.0 as i64))
+            .order(season_standings::season_id)
+            .get_results(&self.pool.get().unwrap())
+    }
+
+    /// Erase everything stored about a user: their XP in every guild, their
+    /// settings, their ledger entries and daily totals, and their places in
+    /// past seasons. Guilds that blocked them keep a row with just the
+    /// block. Returns how many guilds they had XP in
+    ///
+    /// # SQL:
+    /// ```sql
+    /// BEGIN;
+    ///
+    /// DELETE FROM users
+    /// WHERE user_id = <user_id> AND NOT blocked
+    /// RETURNING guild_id;
+    ///
+    /// UPDATE users
+    /// SET xp = 0, last_xp_at = NOW(), left_at = NULL
+    /// WHERE user_id = <user_id> AND blocked
+    /// RETURNING guild_id;
+    ///
+    /// DELETE FROM user_settings WHERE user_id = <user_id>;
+    /// DELETE FROM xp_events WHERE user_id = <user_id>;
+    /// DELETE FROM xp_daily WHERE user_id = <user_id>;
+    /// DELETE FROM season_standings WHERE user_id = <user_id>;
+    ///
+    /// COMMIT;
+    /// ```
+    pub fn delete_user_data(
+        &self,
+        user_id: UserId,
+    ) -> Result<usize, DieselError> {
+        let conn = self.pool.get().unwrap();
+        let uid = user_id.0 as i64;
+
+        let guilds = conn.transaction::<_, DieselError, _>(|| {
+            let user = users::table.filter(users::user_id.eq(uid));
+
+            let mut guilds: Vec<i64> =
+                diesel::delete(user.filter(users::blocked.eq(false)))
+                    .returning(users::guild_id)
+                    .get_results(&conn)?;
+
+            // a block belongs to the guild, so erasing their data can't be
+            // used to get out of one
+            let blocked: Vec<i64> =
+                diesel::update(user.filter(users::blocked.eq(true)))
+                    .set((
+                        users::xp.eq(0),
+                        users::last_xp_at.eq(Utc::now()),
+                        users::left_at.eq(None::<DateTime<Utc>>),
+                    ))
+                    .returning(users::guild_id)
+                    .get_results(&conn)?;
+            guilds.extend(blocked);
+
+            diesel::delete(user_settings::table.find(uid)).execute(&conn)?;
+
+            // the ledger's trigger only stops rows being changed, not
+            // deleted, so a user's history can still be erased
+            diesel::delete(xp_events::table.filter(xp_events::user_id.eq(uid)))
+                .execute(&conn)?;
+            diesel::delete(xp_daily::table.filter(xp_daily::user_id.eq(uid)))
+                .execute(&conn)?;
+            diesel::delete(
+                season_standings::table
+                    .filter(season_standings::user_id.eq(uid)),
+            )
+            .execute(&conn)?;
+
+            Ok(guilds)
+        })?;
+
+        for guild_id in &guilds {
+            self.redis.del_user(&GuildId(*guild_id as u64), &user_id);
+        }
+
+        Ok(guilds.len())
+    }
+
+    /// Erase everything stored for a guild: its settings, everyone's XP,
+    /// level rewards, multipliers, seasons, ledger and daily totals
+    ///
+    /// # SQL:
+    /// ```sql
+    /// BEGIN;
+    ///
+    /// DELETE FROM users WHERE guild_id = <guild_id> RETURNING user_id;
+    /// DELETE FROM level_rewards WHERE guild_id = <guild_id>;
+    /// DELETE FROM xp_multipliers WHERE guild_id = <guild_id>;
+    /// DELETE FROM seasons WHERE guild_id = <guild_id>;
+    /// DELETE FROM xp_events WHERE guild_id = <guild_id>;
+    /// DELETE FROM xp_daily WHERE guild_id = <guild_id>;
+    /// DELETE FROM guilds WHERE guild_id = <guild_id>;
+    ///
+    /// COMMIT;
+    /// ```
+    pub fn delete_guild_data(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<(), DieselError> {
+        let conn = self.pool.get().unwrap();
+        let gid = guild_id.0 as i64;
+
+        let users = conn.transaction::<_, DieselError, _>(|| {
+            let users: Vec<i64> =
+                diesel::delete(users::table.filter(users::guild_id.eq(gid)))
+                    .returning(users::user_id)
+                    .get_results(&conn)?;
+
+            diesel::delete(
+                level_rewards::table.filter(level_rewards::guild_id.eq(gid)),
+            )
+            .execute(&conn)?;
+            diesel::delete(
+                xp_multipliers::table.filter(xp_multipliers::guild_id.eq(gid)),
+            )
+            .execute(&conn)?;
+            // standings go with them, as they cascade
+            diesel::delete(seasons::table.filter(seasons::guild_id.eq(gid)))
+                .execute(&conn)?;
+            diesel::delete(
+                xp_events::table.filter(xp_events::guild_id.eq(gid)),
+            )
+            .execute(&conn)?;
+            diesel::delete(xp_daily::table.filter(xp_daily::guild_id.eq(gid)))
+                .execute(&conn)?;
+            diesel::delete(guilds::table.filter(guilds::guild_id.eq(gid)))
+                .execute(&conn)?;
+
+            Ok(users)
+        })?;
+
+        self.redis.del_guild(&guild_id);
+        self.redis.del_multipliers(&guild_id);
+
+        for user_id in users {
+            self.redis.del_user(&guild_id, &UserId(user_id as u64));
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of a raw `COUNT(*) AS count` query
+#[derive(QueryableByName)]
+struct Count {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// `(xp, user_id) <op> (<xp>, <user_id>)` as a row comparison, which Postgres
+/// answers with a range scan on `users_leaderboard_idx`. The same condition
+/// spelled out with `OR` can't use the index past `guild_id`
+fn leaderboard_cmp(
+    op: &'static str,
+    (xp, user_id): (i32, i64),
+) -> Box<dyn BoxableExpression<users::table, Pg, SqlType = Bool>> {
+    Box::new(
+        sql::<Bool>(&format!("(xp, user_id) {} (", op))
+            .bind::<Integer, _>(xp)
+            .sql(", ")
+            .bind::<BigInt, _>(user_id)
+            .sql(")"),
+    )
 }
@@ -6,9 +6,10 @@ use r2d2_redis::{
 use serenity::model::id::{GuildId, UserId};
 use tracing::debug;
 
-use crate::models::{guild::Guild, user::User};
+use crate::models::{guild::Guild, multiplier::XpMultiplier, user::User};
 
 const GUILD_TTL_SECS: usize = 10;
+const MULTIPLIERS_TTL_SECS: usize = 10;
 const USER_TTL_SECS: usize = 10;
 
 pub struct RedisCache {
@@ -70,6 +71,57 @@ impl RedisCache {
             .unwrap();
     }
 
+    // -- multipliers --
+
+    pub fn del_multipliers(&self, guild: &GuildId) {
+        debug!("RedisCache#del_multipliers");
+        let _: () = self
+            .pool
+            .get()
+            .unwrap()
+            .del(self.format_multipliers_key(guild.0))
+            .unwrap();
+    }
+
+    pub fn get_multipliers(
+        &self,
+        guild: &GuildId,
+    ) -> Option<Vec<XpMultiplier>> {
+        debug!("RedisCache#get_multipliers");
+        let m: Option<String> = self
+            .pool
+            .get()
+            .unwrap()
+            .get(self.format_multipliers_key(guild.0))
+            .unwrap();
+
+        if let Some(m) = m {
+            serde_json::from_str(&m).unwrap()
+        } else {
+            None
+        }
+    }
+
+    pub fn set_multipliers(
+        &self,
+        guild: &GuildId,
+        multipliers: &[XpMultiplier],
+    ) {
+        debug!("RedisCache#set_multipliers");
+        let json = serde_json::to_string(&multipliers).unwrap();
+
+        let _: () = self
+            .pool
+            .get()
+            .unwrap()
+            .set_ex(
+                self.format_multipliers_key(guild.0),
+                json,
+                MULTIPLIERS_TTL_SECS,
+            )
+            .unwrap();
+    }
+
     // -- users --
 
     pub fn del_user(&self, guild: &GuildId, user: &UserId) {
@@ -130,7 +182,7 @@ impl RedisCache {
 
         let users = from_redis
             .iter()
-            .map(|u| serde_json::from_str(&u).unwrap())
+            .map(|u| serde_json::from_str(u).unwrap())
             .collect::<Vec<User>>();
 
         println!("{:#?}", users);
@@ -145,4 +197,8 @@ impl RedisCache {
     fn format_guild_key(&self, guild: u64) -> String {
         format!("guilds:{}", guild)
     }
+
+    fn format_multipliers_key(&self, guild: u64) -> String {
+        format!("multipliers:{}", guild)
+    }
 }
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use chrono::{Duration as ChronoDuration, NaiveDate, Utc};
+use serenity::{model::prelude::*, prelude::*};
+use tracing::{error, info};
+
+use crate::{db::postgres::Database, models::guild::Guild};
+
+/// How often guilds are checked for decay that's due
+const DECAY_TICK: Duration = Duration::from_secs(60 * 60);
+/// How many members are updated by each query, so the database isn't held
+/// for long on large guilds
+const DECAY_BATCH_SIZE: i64 = 500;
+/// The most days of decay made up for at once, if the bot was down when it
+/// should have run
+const DECAY_CATCH_UP_DAYS: i64 = 7;
+
+/// Spawn the task that applies XP decay once a day (UTC) in every guild that
+/// has it turned on
+pub fn start_decay_ticker(ctx: Context) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DECAY_TICK);
+
+        loop {
+            interval.tick().await;
+            run_decay(&ctx).await;
+        }
+    });
+}
+
+async fn run_decay(ctx: &Context) {
+    let today = Utc::now().naive_utc().date();
+
+    let guilds = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        match db.get_guilds_due_decay(today) {
+            Ok(g) => g,
+            Err(e) => {
+                error!("Failed to get guilds due decay: {:?}", e);
+                return;
+            },
+        }
+    };
+
+    for guild in guilds {
+        if let Err(e) = decay_guild(ctx, &guild, today).await {
+            error!("Failed to decay XP in guild {}: {:?}", guild.guild_id, e);
+        }
+    }
+}
+
+/// Apply the decay a guild is owed, one batch of members at a time. Members
+/// who went past the grace period while the bot was down only owe the days
+/// since then. The database lock is let go between batches so XP can still be
+/// earned
+async fn decay_guild(
+    ctx: &Context,
+    guild: &Guild,
+    today: NaiveDate,
+) -> Result<(), diesel::result::Error> {
+    let days = guild
+        .decay_last_run
+        .map_or(1, |last| (today - last).num_days())
+        .clamp(1, DECAY_CATCH_UP_DAYS) as i32;
+    let cutoff =
+        Utc::now() - ChronoDuration::days(guild.decay_grace_days as i64);
+    // a curve that can't reach the minimum level keeps members at the
+    // highest level it can, rather than letting them decay to nothing
+    let curve = guild.curve();
+    let floor = curve
+        .xp_for_level(guild.decay_min_level.min(curve.max_level()))
+        .unwrap_or(0)
+        .min(i32::MAX as i64) as i32;
+
+    let mut after_id = 0;
+    let mut decayed = 0;
+
+    loop {
+        let batch = {
+            let data = ctx.data.read().await;
+            let db = data
+                .get::<Database>()
+                .expect("Expected `Database` in TypeMap")
+                .lock()
+                .await;
+
+            db.decay_guild_users(
+                guild,
+                cutoff,
+                floor,
+                days,
+                after_id,
+                DECAY_BATCH_SIZE,
+            )?
+        };
+
+        decayed += batch.len();
+
+        match batch.last() {
+            Some(u) if batch.len() as i64 == DECAY_BATCH_SIZE => {
+                after_id = u.id
+            },
+            _ => break,
+        }
+    }
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    db.set_guild_decay_last_run(GuildId(guild.guild_id as u64), today)?;
+
+    info!(
+        "Decayed XP for {} members in guild {} ({} days)",
+        decayed, guild.guild_id, days
+    );
+
+    Ok(())
+}
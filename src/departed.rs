@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use chrono::{Duration as ChronoDuration, Utc};
+use serenity::{model::prelude::*, prelude::*};
+use tracing::{error, info};
+
+use crate::{db::postgres::Database, models::guild::DepartedMode};
+
+/// How often guilds the bot was removed from are checked for data to purge
+const RETENTION_TICK: Duration = Duration::from_secs(60 * 60);
+/// How long a guild's data is kept after the bot is removed from it, so
+/// nothing is lost if it's added back, unless `GUILD_RETENTION_DAYS` is set
+pub const DEFAULT_GUILD_RETENTION_DAYS: i64 = 30;
+
+/// Keep, hide or delete a member's XP when they leave a guild, depending on
+/// the guild's settings
+pub async fn handle_member_left(ctx: &Context, guild_id: GuildId, user: &User) {
+    if user.bot {
+        return;
+    }
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let result = match db.get_guild(guild_id).map(|g| g.departed_mode()) {
+        Ok(DepartedMode::Keep) => Ok(()),
+        Ok(DepartedMode::Hide) => {
+            db.set_guild_user_left(user.id, guild_id, true)
+        },
+        Ok(DepartedMode::Delete) => {
+            db.delete_guild_user(user.id, guild_id).map(|_| ())
+        },
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        error!(
+            "Failed to handle member {} leaving guild {}: {:?}",
+            user.id, guild_id, e
+        );
+    }
+}
+
+/// Show a member on the leaderboards again when they come back to a guild
+/// that hid them
+pub async fn handle_member_joined(
+    ctx: &Context,
+    guild_id: GuildId,
+    user: &User,
+) {
+    if user.bot {
+        return;
+    }
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    if let Err(e) = db.set_guild_user_left(user.id, guild_id, false) {
+        error!(
+            "Failed to handle member {} rejoining guild {}: {:?}",
+            user.id, guild_id, e
+        );
+    }
+}
+
+/// Record that the bot was removed from a guild, which starts its retention
+/// window. Guilds that are only unavailable because of an outage are left
+/// alone
+pub async fn handle_guild_left(ctx: &Context, guild: &GuildUnavailable) {
+    if guild.unavailable {
+        return;
+    }
+
+    set_guild_left(ctx, guild.id, true).await;
+}
+
+/// Record that the bot is in a guild, which stops its retention window if
+/// it was removed before
+pub async fn handle_guild_joined(ctx: &Context, guild_id: GuildId) {
+    set_guild_left(ctx, guild_id, false).await;
+}
+
+async fn set_guild_left(ctx: &Context, guild_id: GuildId, left: bool) {
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    if let Err(e) = db.set_guild_left(guild_id, left) {
+        error!(
+            "Failed to record the bot {} guild {}: {:?}",
+            if left { "leaving" } else { "joining" },
+            guild_id,
+            e
+        );
+    }
+}
+
+/// Spawn the task that erases the data of guilds the bot was removed from
+/// more than `retention_days` ago
+pub fn start_retention_ticker(ctx: Context, retention_days: i64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_TICK);
+
+        loop {
+            interval.tick().await;
+            purge_departed_guilds(&ctx, retention_days).await;
+        }
+    });
+}
+
+async fn purge_departed_guilds(ctx: &Context, retention_days: i64) {
+    let cutoff = Utc::now() - ChronoDuration::days(retention_days);
+
+    let data = ctx.data.read().await;
+    let db = data
+        .get::<Database>()
+        .expect("Expected `Database` in TypeMap")
+        .lock()
+        .await;
+
+    let guilds = match db.get_guilds_left_before(cutoff) {
+        Ok(g) => g,
+        Err(e) => {
+            error!("Failed to get guilds past retention: {:?}", e);
+            return;
+        },
+    };
+
+    for guild in guilds {
+        match db.delete_guild_data(GuildId(guild.guild_id as u64)) {
+            Ok(()) => info!(
+                "Erased guild {}, which the bot left over {} days ago",
+                guild.guild_id, retention_days
+            ),
+            Err(e) => {
+                error!("Failed to erase guild {}: {:?}", guild.guild_id, e)
+            },
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{fmt, str::FromStr};
+
+use serde::Serialize;
+
+/// The biggest file that can be uploaded to Discord, in bytes
+pub const EXPORT_SIZE_LIMIT: usize = 8 * 1024 * 1024;
+/// How many members are read from the database at a time, so large guilds
+/// never have to be held in memory all at once
+pub const EXPORT_BATCH_SIZE: i64 = 1000;
+
+/// The kind of file a leaderboard is exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" | "spreadsheet" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A member's line in an exported leaderboard
+#[derive(Debug, Serialize)]
+pub struct ExportRow {
+    /// A string, since IDs are too big for some JSON readers
+    pub user_id: String,
+    /// The member's name and tag, if the bot has them cached
+    pub username: Option<String>,
+    pub xp: i32,
+    pub level: i32,
+    /// The member's position on the leaderboard, or `None` if they're
+    /// blocked or hidden and don't show up on it
+    pub rank: Option<i64>,
+    pub blocked: bool,
+    /// Whether the member left and is hidden until they come back
+    pub hidden: bool,
+}
+
+/// Builds an export file one row at a time
+#[derive(Debug)]
+pub struct ExportWriter {
+    format: ExportFormat,
+    out: Vec<u8>,
+    rows: usize,
+}
+
+impl ExportWriter {
+    pub fn new(format: ExportFormat) -> Self {
+        let out = match format {
+            ExportFormat::Csv => {
+                b"user_id,username,xp,level,rank,blocked,hidden\n".to_vec()
+            },
+            ExportFormat::Json => b"[".to_vec(),
+        };
+
+        Self {
+            format,
+            out,
+            rows: 0,
+        }
+    }
+
+    pub fn write(&mut self, row: &ExportRow) {
+        match self.format {
+            ExportFormat::Csv => {
+                let line = format!(
+                    "{},{},{},{},{},{},{}\n",
+                    row.user_id,
+                    row.username.as_deref().map_or_else(String::new, csv_cell),
+                    row.xp,
+                    row.level,
+                    row.rank.map_or_else(String::new, |r| r.to_string()),
+                    row.blocked,
+                    row.hidden
+                );
+
+                self.out.extend_from_slice(line.as_bytes());
+            },
+            ExportFormat::Json => {
+                if self.rows > 0 {
+                    self.out.push(b',');
+                }
+
+                self.out.push(b'\n');
+                // writing to a `Vec` can't fail, and the row has no maps
+                // with non-string keys
+                serde_json::to_writer(&mut self.out, row).unwrap();
+            },
+        }
+
+        self.rows += 1;
+    }
+
+    /// How many rows have been written
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The size of the file so far, in bytes
+    pub fn size(&self) -> usize {
+        self.out.len()
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.format == ExportFormat::Json {
+            self.out.extend_from_slice(b"\n]\n");
+        }
+
+        self.out
+    }
+}
+
+/// Quote a CSV cell if it needs it. Cells that a spreadsheet would read as a
+/// formula are prefixed with `'` so a username can't run one
+fn csv_cell(s: &str) -> String {
+    let s = if s.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", s)
+    } else {
+        s.to_string()
+    };
+
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
+    }
+}
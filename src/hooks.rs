@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use rand::Rng;
 use serenity::{
     framework::standard::macros::hook,
@@ -7,9 +9,10 @@ use serenity::{
 
 use crate::{
     db::postgres::Database,
+    levelup::{apply_multipliers, grant_xp},
+    models::xp_event::XpSource,
     MessageXPTimeoutCache,
-    MAX_MESSAGE_XP,
-    MIN_MESSAGE_XP,
+    MESSAGE_XP_LIMIT,
 };
 
 #[hook]
@@ -18,46 +21,76 @@ pub async fn normal_message(ctx: &Context, msg: &Message) {
         return;
     }
 
-    let data = ctx.data.read().await;
+    let guild_id = msg.guild_id.unwrap();
 
-    let mut timeout_cache = data
-        .get::<MessageXPTimeoutCache>()
-        .expect("Expected `MessageXPTimeoutCache` in TypeMap")
-        .lock()
-        .await;
+    let category = ctx
+        .cache
+        .guild_channel_field(msg.channel_id, |c| c.category_id)
+        .await
+        .flatten();
+    let roles = msg
+        .member
+        .as_ref()
+        .map(|m| m.roles.as_slice())
+        .unwrap_or_default();
 
-    let cache_key = (msg.author.id, msg.guild_id.unwrap());
+    let (guild, xp_to_grant) = {
+        let data = ctx.data.read().await;
 
-    if timeout_cache.peek(&cache_key).is_some() {
-        return;
-    }
+        let db = data
+            .get::<Database>()
+            .expect("expected `database` in typemap")
+            .lock()
+            .await;
 
-    let db = data
-        .get::<Database>()
-        .expect("expected `database` in typemap")
-        .lock()
-        .await;
-
-    let xp_to_grant: i32 =
-        rand::thread_rng().gen_range(MIN_MESSAGE_XP..=MAX_MESSAGE_XP);
-
-    if let Ok(saved) =
-        db.add_guild_user_xp(msg.author.id, msg.guild_id.unwrap(), xp_to_grant)
-    {
-        println!("Saved: {:#?}", saved);
-        let prev_amt = saved.xp - xp_to_grant;
-        let curr_amt = saved.xp;
-
-        let prev_lvl = crate::util::xp::xp_to_lvl(prev_amt);
-        let curr_lvl = crate::util::xp::xp_to_lvl(curr_amt);
-
-        if prev_lvl != curr_lvl {
-            msg.channel_id
-                .say(&ctx.http, format!("Level {}", curr_lvl))
-                .await
-                .ok();
+        let guild = match db.get_guild(guild_id) {
+            Ok(g) => g,
+            Err(_) => return,
+        };
+
+        if !guild.earns_xp(msg.channel_id, category, roles) {
+            return;
         }
-    }
 
-    timeout_cache.insert(cache_key, 0);
+        let mut timeout_cache = data
+            .get::<MessageXPTimeoutCache>()
+            .expect("Expected `MessageXPTimeoutCache` in TypeMap")
+            .lock()
+            .await;
+
+        let cache_key = (msg.author.id, guild_id);
+        let timeout = Duration::from_secs(guild.xp_timeout_secs as u64);
+
+        if let Some(last_grant) = timeout_cache.peek(&cache_key) {
+            if last_grant.elapsed() < timeout {
+                return;
+            }
+        }
+
+        timeout_cache.insert(cache_key, Instant::now());
+
+        let multipliers = db.get_xp_multipliers(guild_id).unwrap_or_default();
+        let roll = rand::thread_rng().gen_range(guild.min_xp..=guild.max_xp);
+        let xp_to_grant = apply_multipliers(
+            &guild,
+            &multipliers,
+            roll,
+            MESSAGE_XP_LIMIT,
+            msg.channel_id,
+            category,
+            roles,
+        );
+
+        (guild, xp_to_grant)
+    };
+
+    grant_xp(
+        ctx,
+        &guild,
+        &msg.author,
+        xp_to_grant,
+        XpSource::Message,
+        Some(msg.channel_id),
+    )
+    .await;
 }
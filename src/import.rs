@@ -0,0 +1,357 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use serde_json::Value;
+
+use crate::util::curve::{LevelCurve, MAX_LEVEL};
+
+/// The biggest leaderboard file that will be read, in bytes
+pub const IMPORT_SIZE_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// The names a member's ID is given in other bots' exports
+const ID_FIELDS: &[&str] = &["id", "user_id", "userid", "uid", "discord_id"];
+/// The names a member's XP is given in other bots' exports
+const XP_FIELDS: &[&str] = &["xp", "exp", "score", "total_xp", "experience"];
+/// The names a member's level is given in other bots' exports
+const LEVEL_FIELDS: &[&str] = &["level", "lvl"];
+
+/// The leveling bot a leaderboard was exported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// MEE6's leaderboard API: `{"players": [{"id", "xp", "level"}]}`
+    Mee6,
+    /// Tatsu's ranking API: `{"rankings": [{"user_id", "score"}]}`
+    Tatsu,
+    /// Amari's leaderboard API: `{"data": [{"id", "exp", "level"}]}`
+    Amari,
+    /// A CSV file with a header row naming the ID, XP and (optionally) level
+    /// columns
+    Csv,
+}
+
+impl ImportFormat {
+    /// The curve the bot levels members up on, if it's known. Without one,
+    /// members keep the level the export says they're at, if it says
+    pub fn curve(&self) -> Option<LevelCurve> {
+        match self {
+            Self::Mee6 => Some(LevelCurve::Quadratic),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Mee6 => "MEE6",
+            Self::Tatsu => "Tatsu",
+            Self::Amari => "Amari",
+            Self::Csv => "CSV",
+        })
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mee6" => Ok(Self::Mee6),
+            "tatsu" | "tatsumaki" => Ok(Self::Tatsu),
+            "amari" => Ok(Self::Amari),
+            "csv" => Ok(Self::Csv),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What happens to a member who already has XP here
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Replace their XP with the imported XP
+    Overwrite,
+    /// Keep whichever is higher
+    KeepMax,
+    /// Add the imported XP to theirs
+    Add,
+}
+
+impl ImportMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Overwrite => "overwrite",
+            Self::KeepMax => "max",
+            Self::Add => "add",
+        }
+    }
+
+    /// The XP a member with `old` XP ends up with. This has to match the
+    /// upsert in `Database::import_guild_users`
+    pub fn merge(&self, old: i32, imported: i32) -> i32 {
+        match self {
+            Self::Overwrite => imported,
+            Self::KeepMax => old.max(imported),
+            Self::Add => old.saturating_add(imported),
+        }
+    }
+}
+
+impl fmt::Display for ImportMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ImportMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "overwrite" | "replace" => Ok(Self::Overwrite),
+            "max" | "keep_max" | "keepmax" => Ok(Self::KeepMax),
+            "add" | "sum" => Ok(Self::Add),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A member's standing on another bot's leaderboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportEntry {
+    pub user_id: u64,
+    pub xp: i64,
+    pub level: Option<i32>,
+}
+
+/// Everyone read from a leaderboard export
+#[derive(Debug)]
+pub struct ImportedLeaderboard {
+    pub format: ImportFormat,
+    /// One entry per member. If a member is listed more than once, their
+    /// highest XP is kept
+    pub entries: Vec<ImportEntry>,
+    /// Rows that didn't have a member ID and XP that could be read
+    pub skipped: usize,
+}
+
+/// Read a leaderboard exported from another bot. The format is worked out
+/// from the file unless `format` is given
+pub fn parse_leaderboard(
+    data: &[u8],
+    format: Option<ImportFormat>,
+) -> Result<ImportedLeaderboard, String> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| "The leaderboard file isn't text".to_string())?
+        .trim_start_matches('\u{feff}');
+
+    let (format, rows) = match format {
+        Some(ImportFormat::Csv) => (ImportFormat::Csv, read_csv(text)?),
+        _ => match serde_json::from_str::<Value>(text) {
+            Ok(json) => read_json(&json, format)?,
+            Err(_) if format.is_none() => (ImportFormat::Csv, read_csv(text)?),
+            Err(e) => return Err(format!("That isn't valid JSON: {}", e)),
+        },
+    };
+
+    let total = rows.len();
+    let mut entries: HashMap<u64, ImportEntry> = HashMap::new();
+
+    for entry in rows.into_iter().flatten() {
+        entries
+            .entry(entry.user_id)
+            .and_modify(|e| {
+                if entry.xp > e.xp {
+                    *e = entry;
+                }
+            })
+            .or_insert(entry);
+    }
+
+    let entries = entries.into_values().collect::<Vec<ImportEntry>>();
+    let skipped = total - entries.len();
+
+    if entries.is_empty() {
+        return Err("Couldn't find any members in the leaderboard".to_string());
+    }
+
+    Ok(ImportedLeaderboard {
+        format,
+        entries,
+        skipped,
+    })
+}
+
+/// Work out the XP a member should have on `target` so they stay at the
+/// level they were at on the other bot, and as far through it as they were
+pub fn map_xp(
+    entry: &ImportEntry,
+    source: Option<&LevelCurve>,
+    target: &LevelCurve,
+) -> i32 {
+    let xp = entry.xp.clamp(0, i32::MAX as i64);
+
+    let mapped = match (source, entry.level) {
+        (Some(curve), _) => {
+            let p = curve.progress(xp as i32);
+
+            match (
+                target.xp_for_level(p.level),
+                target.xp_for_level(p.level + 1),
+                p.level_size(),
+            ) {
+                (Some(start), Some(end), Some(size)) => {
+                    let into = (end - start) as i128 * p.xp_into_level as i128;
+                    start.saturating_add((into / size as i128) as i64)
+                },
+                (Some(start), ..) => start,
+                _ => xp,
+            }
+        },
+        // without the other bot's curve, the best that can be done is to
+        // keep the XP if it's in the right level here, or move it to the
+        // nearest end of that level if it isn't
+        (None, Some(level)) => {
+            let level = level.clamp(0, MAX_LEVEL);
+
+            match target.xp_for_level(level) {
+                Some(start) => {
+                    let end = target
+                        .xp_for_level(level + 1)
+                        .map_or(i64::MAX, |next| next - 1);
+                    xp.clamp(start, end.max(start))
+                },
+                None => xp,
+            }
+        },
+        (None, None) => xp,
+    };
+
+    mapped.clamp(0, i32::MAX as i64) as i32
+}
+
+/// Find the list of members in a JSON export, and which bot it came from
+fn read_json(
+    json: &Value,
+    format: Option<ImportFormat>,
+) -> Result<(ImportFormat, Vec<Option<ImportEntry>>), String> {
+    let (detected, list) = match json {
+        Value::Object(o) => [
+            ("players", ImportFormat::Mee6),
+            ("rankings", ImportFormat::Tatsu),
+            ("data", ImportFormat::Amari),
+        ]
+        .iter()
+        .find_map(|(key, f)| o.get(*key).map(|list| (Some(*f), list)))
+        .ok_or_else(|| {
+            "Expected a MEE6, Tatsu or Amari leaderboard".to_string()
+        })?,
+        list => (None, list),
+    };
+
+    let rows = list
+        .as_array()
+        .ok_or_else(|| "Couldn't find the list of members".to_string())?;
+
+    // a bare list doesn't say where it's from, so go by what the XP is called
+    let format = format.or(detected).unwrap_or_else(|| {
+        let first = rows.first();
+        let has = |key: &str| first.and_then(|r| r.get(key)).is_some();
+
+        if has("score") {
+            ImportFormat::Tatsu
+        } else if has("exp") {
+            ImportFormat::Amari
+        } else {
+            ImportFormat::Mee6
+        }
+    });
+
+    let entries = rows
+        .iter()
+        .map(|row| {
+            let field = |names: &[&str]| {
+                names.iter().find_map(|n| row.get(*n)).and_then(json_number)
+            };
+
+            Some(ImportEntry {
+                user_id: field(ID_FIELDS)?.parse().ok()?,
+                xp: field(XP_FIELDS)?.parse().ok()?,
+                level: field(LEVEL_FIELDS).and_then(|l| l.parse().ok()),
+            })
+        })
+        .collect();
+
+    Ok((format, entries))
+}
+
+/// The text of a JSON number, or of a string holding one. IDs are usually
+/// strings, since they're too big for some JSON readers
+fn json_number(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Read a CSV export, finding the columns by the names in its header row
+fn read_csv(text: &str) -> Result<Vec<Option<ImportEntry>>, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+
+    let header = lines
+        .next()
+        .map(split_csv_line)
+        .ok_or_else(|| "The leaderboard file is empty".to_string())?;
+
+    let column = |names: &[&str]| {
+        header.iter().position(|h| {
+            let h = h.to_lowercase().replace(' ', "_");
+            names.contains(&h.as_str())
+        })
+    };
+
+    let (id, xp, level) = match (column(ID_FIELDS), column(XP_FIELDS)) {
+        (Some(id), Some(xp)) => (id, xp, column(LEVEL_FIELDS)),
+        _ => {
+            return Err("Expected a header row with a column for member IDs \
+                        and one for XP"
+                .to_string())
+        },
+    };
+
+    let entries = lines
+        .map(|line| {
+            let cells = split_csv_line(line);
+            let cell = |i: usize| cells.get(i).map(|c| c.replace(',', ""));
+
+            Some(ImportEntry {
+                user_id: cell(id)?.parse().ok()?,
+                xp: cell(xp)?.parse().ok()?,
+                level: level.and_then(cell).and_then(|l| l.parse().ok()),
+            })
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+/// Split a line of CSV into its cells, allowing for quoted cells with commas
+/// or escaped (doubled) quotes in them
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut cells = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cells.last_mut().unwrap().push('"');
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+
+    cells.iter().map(|c| c.trim().to_string()).collect()
+}
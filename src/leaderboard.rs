@@ -0,0 +1,427 @@
+use std::{fmt, time::Duration};
+
+use chrono::{Datelike, Duration as ChronoDuration, NaiveDate, Utc};
+use serenity::{model::prelude::*, prelude::*};
+
+use crate::{
+    db::postgres::Database,
+    models::{
+        season::SeasonStanding,
+        user::{User, WindowUser},
+    },
+    util::curve::LevelCurve,
+    LeaderboardPages,
+};
+
+/// How many members are shown on each page of the leaderboard
+pub const PAGE_SIZE: i64 = 10;
+/// How long a leaderboard's page controls keep working after the last use
+pub const PAGE_CONTROLS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+const PREV_PAGE: &str = "◀️";
+const NEXT_PAGE: &str = "▶️";
+
+/// The days a leaderboard only counts XP from. Days start at midnight UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Today,
+    /// Since Monday
+    Week,
+    /// Since the 1st
+    Month,
+    /// Between two days, inclusive
+    Range(NaiveDate, NaiveDate),
+}
+
+impl Window {
+    /// The first and last day of the window, worked out from today's date
+    pub fn days(&self) -> (NaiveDate, NaiveDate) {
+        let today = Utc::now().naive_utc().date();
+
+        match *self {
+            Self::Today => (today, today),
+            Self::Week => {
+                let since_monday = today.weekday().num_days_from_monday();
+                (today - ChronoDuration::days(since_monday as i64), today)
+            },
+            Self::Month => (today.with_day(1).unwrap(), today),
+            Self::Range(from, to) => (from, to),
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Today => write!(f, "today"),
+            Self::Week => write!(f, "this week"),
+            Self::Month => write!(f, "this month"),
+            Self::Range(from, to) if from == to => write!(f, "{}", from),
+            Self::Range(from, to) => write!(f, "{} to {}", from, to),
+        }
+    }
+}
+
+/// Which XP a leaderboard ranks members by
+#[derive(Debug, Clone, Copy)]
+enum Board {
+    AllTime,
+    Window(Window),
+    /// A finished season's final standings
+    Season {
+        id: i32,
+        number: i32,
+    },
+}
+
+/// The page a leaderboard message is showing, and the members at either end
+/// of it so the next or previous page can be found without an offset
+#[derive(Debug, Clone)]
+pub struct LeaderboardPage {
+    pub guild_id: GuildId,
+    pub page: i64,
+    pub pages: i64,
+    board: Board,
+    first: Option<(i32, i64)>,
+    last: Option<(i32, i64)>,
+    content: String,
+}
+
+impl LeaderboardPage {
+    fn new(
+        guild_id: GuildId,
+        page: i64,
+        pages: i64,
+        users: &[User],
+        curve: &LevelCurve,
+    ) -> Self {
+        let key = |u: &User| (u.xp, u.user_id);
+
+        Self {
+            guild_id,
+            page,
+            pages,
+            board: Board::AllTime,
+            first: users.first().map(key),
+            last: users.last().map(key),
+            content: format_page(page, pages, users, curve),
+        }
+    }
+
+    fn windowed(
+        guild_id: GuildId,
+        window: Window,
+        page: i64,
+        pages: i64,
+        users: &[WindowUser],
+    ) -> Self {
+        Self {
+            guild_id,
+            page,
+            pages,
+            board: Board::Window(window),
+            first: None,
+            last: None,
+            content: format_window_page(window, page, pages, users),
+        }
+    }
+
+    fn season(
+        guild_id: GuildId,
+        id: i32,
+        number: i32,
+        page: i64,
+        pages: i64,
+        standings: &[SeasonStanding],
+    ) -> Self {
+        Self {
+            guild_id,
+            page,
+            pages,
+            board: Board::Season { id, number },
+            first: None,
+            last: None,
+            content: format_season_page(number, page, pages, standings),
+        }
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+/// Load a page of the leaderboard by its number. Pages past the end show the
+/// last page
+pub fn load_page(
+    db: &Database,
+    guild_id: GuildId,
+    page: i64,
+) -> Result<LeaderboardPage, diesel::result::Error> {
+    let count = db.count_leaderboard_users(guild_id)?;
+    let pages = ((count + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    let page = page.clamp(1, pages);
+
+    let users =
+        db.get_leaderboard_page(guild_id, (page - 1) * PAGE_SIZE, PAGE_SIZE)?;
+    let curve = db.get_guild(guild_id)?.curve();
+
+    Ok(LeaderboardPage::new(guild_id, page, pages, &users, &curve))
+}
+
+/// Load a page of the leaderboard for the XP earned in `window`. Pages past
+/// the end show the last page
+pub fn load_window_page(
+    db: &Database,
+    guild_id: GuildId,
+    window: Window,
+    page: i64,
+) -> Result<LeaderboardPage, diesel::result::Error> {
+    let (from, to) = window.days();
+
+    let count = db.count_window_users(guild_id, from, to)?;
+    let pages = ((count + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    let page = page.clamp(1, pages);
+
+    let users = db.get_window_leaderboard(
+        guild_id,
+        from,
+        to,
+        (page - 1) * PAGE_SIZE,
+        PAGE_SIZE,
+    )?;
+
+    Ok(LeaderboardPage::windowed(
+        guild_id, window, page, pages, &users,
+    ))
+}
+
+/// Load a page of a finished season's final standings, by the season's row
+/// ID and number. Pages past the end show the last page
+pub fn load_season_page(
+    db: &Database,
+    guild_id: GuildId,
+    season_id: i32,
+    number: i32,
+    page: i64,
+) -> Result<LeaderboardPage, diesel::result::Error> {
+    let count = db.count_season_standings(season_id)?;
+    let pages = ((count + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    let page = page.clamp(1, pages);
+
+    let standings =
+        db.get_season_standings(season_id, (page - 1) * PAGE_SIZE, PAGE_SIZE)?;
+
+    Ok(LeaderboardPage::season(
+        guild_id, season_id, number, page, pages, &standings,
+    ))
+}
+
+/// Add the page controls to a leaderboard message and start listening for
+/// them
+pub async fn add_page_controls(
+    ctx: &Context,
+    msg: &Message,
+    page: LeaderboardPage,
+) -> serenity::Result<()> {
+    if page.pages <= 1 {
+        return Ok(());
+    }
+
+    {
+        let data = ctx.data.read().await;
+        data.get::<LeaderboardPages>()
+            .expect("Expected `LeaderboardPages` in TypeMap")
+            .lock()
+            .await
+            .insert(msg.id, page);
+    }
+
+    msg.react(&ctx.http, ReactionType::Unicode(PREV_PAGE.to_string()))
+        .await?;
+    msg.react(&ctx.http, ReactionType::Unicode(NEXT_PAGE.to_string()))
+        .await?;
+
+    Ok(())
+}
+
+/// Turn the page of a leaderboard message when someone uses its controls
+pub async fn handle_page_reaction(ctx: &Context, reaction: &Reaction) {
+    let forward = match &reaction.emoji {
+        ReactionType::Unicode(e) if e == NEXT_PAGE => true,
+        ReactionType::Unicode(e) if e == PREV_PAGE => false,
+        _ => return,
+    };
+
+    if reaction.user_id == Some(ctx.cache.current_user_id().await) {
+        return;
+    }
+
+    let data = ctx.data.read().await;
+    let pages = data
+        .get::<LeaderboardPages>()
+        .expect("Expected `LeaderboardPages` in TypeMap");
+
+    let current = match pages.lock().await.get(&reaction.message_id) {
+        Some(p) => p.clone(),
+        None => return,
+    };
+
+    // let whoever reacted use the same control again
+    reaction.delete(&ctx).await.ok();
+
+    let next = {
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        turn_page(&db, &current, forward)
+    };
+
+    let next = match next {
+        Ok(Some(p)) => p,
+        _ => return,
+    };
+
+    let edited = reaction
+        .channel_id
+        .edit_message(&ctx.http, reaction.message_id, |m| {
+            m.content(next.content())
+        })
+        .await;
+
+    if edited.is_ok() {
+        pages.lock().await.insert(reaction.message_id, next);
+    }
+}
+
+/// Load the page after (or before) `current`, or `None` if there isn't one
+fn turn_page(
+    db: &Database,
+    current: &LeaderboardPage,
+    forward: bool,
+) -> Result<Option<LeaderboardPage>, diesel::result::Error> {
+    let (page, edge) = if forward {
+        (current.page + 1, current.last)
+    } else {
+        (current.page - 1, current.first)
+    };
+
+    let in_range = (1..=current.pages).contains(&page);
+
+    // windowed totals are summed on the fly and seasons are archived, so
+    // there's no index for a keyset to use and an offset is just as cheap
+    match current.board {
+        Board::AllTime => {},
+        _ if !in_range => return Ok(None),
+        Board::Window(window) => {
+            return load_window_page(db, current.guild_id, window, page)
+                .map(Some);
+        },
+        Board::Season { id, number } => {
+            return load_season_page(db, current.guild_id, id, number, page)
+                .map(Some);
+        },
+    }
+
+    let edge = match edge {
+        Some(e) if in_range => e,
+        _ => return Ok(None),
+    };
+
+    let users = if forward {
+        db.get_leaderboard_after(current.guild_id, edge, PAGE_SIZE)?
+    } else {
+        db.get_leaderboard_before(current.guild_id, edge, PAGE_SIZE)?
+    };
+
+    if users.is_empty() {
+        return Ok(None);
+    }
+
+    let curve = db.get_guild(current.guild_id)?.curve();
+
+    Ok(Some(LeaderboardPage::new(
+        current.guild_id,
+        page,
+        current.pages,
+        &users,
+        &curve,
+    )))
+}
+
+fn format_page(
+    page: i64,
+    pages: i64,
+    users: &[User],
+    curve: &LevelCurve,
+) -> String {
+    let mut m = format!("**Leaderboard** (page {}/{})", page, pages);
+
+    if users.is_empty() {
+        m.push_str("\nNobody has earned any XP yet");
+    }
+
+    for (i, u) in users.iter().enumerate() {
+        m.push_str(&format!(
+            "\n{}. <@!{}> (level {}, {} XP)",
+            (page - 1) * PAGE_SIZE + i as i64 + 1,
+            u.user_id,
+            curve.level_for_xp(u.xp),
+            u.xp
+        ));
+    }
+
+    m
+}
+
+fn format_window_page(
+    window: Window,
+    page: i64,
+    pages: i64,
+    users: &[WindowUser],
+) -> String {
+    let mut m =
+        format!("**Leaderboard** for {} (page {}/{})", window, page, pages);
+
+    if users.is_empty() {
+        m.push_str("\nNobody has earned any XP yet");
+    }
+
+    for (i, u) in users.iter().enumerate() {
+        m.push_str(&format!(
+            "\n{}. <@!{}> ({} XP)",
+            (page - 1) * PAGE_SIZE + i as i64 + 1,
+            u.user_id,
+            u.xp
+        ));
+    }
+
+    m
+}
+
+fn format_season_page(
+    number: i32,
+    page: i64,
+    pages: i64,
+    standings: &[SeasonStanding],
+) -> String {
+    let mut m = format!(
+        "**Season {} final standings** (page {}/{})",
+        number, page, pages
+    );
+
+    if standings.is_empty() {
+        m.push_str("\nNobody earned any XP that season");
+    }
+
+    for s in standings {
+        m.push_str(&format!(
+            "\n{}. <@!{}> ({} XP)",
+            s.position, s.user_id, s.xp
+        ));
+    }
+
+    m
+}
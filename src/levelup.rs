@@ -0,0 +1,461 @@
+use std::{collections::HashMap, fmt};
+
+use serenity::{model::prelude::*, prelude::*, Error as SerenityError};
+use tracing::{debug, error, warn};
+
+use crate::{
+    db::postgres::Database,
+    models::{
+        guild::{AnnounceMode, Guild as GuildSettings},
+        multiplier::XpMultiplier,
+        reward::LevelReward,
+        xp_event::XpSource,
+    },
+    util::template::{render_level_up, LevelUpVars},
+};
+
+/// Why a reward role couldn't be given to a member
+#[derive(Debug)]
+pub enum RewardError {
+    /// The guild isn't in the cache, so permissions can't be checked
+    GuildUnavailable,
+    /// The bot doesn't have the Manage Roles permission
+    MissingPermissions,
+    /// The role is at or above the bot's highest role
+    RoleTooHigh(String),
+    /// The role has been deleted
+    UnknownRole(RoleId),
+    /// Discord rejected the request
+    Http(SerenityError),
+}
+
+impl fmt::Display for RewardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GuildUnavailable => {
+                write!(f, "This server isn't available right now")
+            },
+            Self::MissingPermissions => write!(
+                f,
+                "I need the **Manage Roles** permission to give out level \
+                 rewards"
+            ),
+            Self::RoleTooHigh(name) => write!(
+                f,
+                "I can't give out the **{}** role because it isn't below my \
+                 highest role",
+                name
+            ),
+            Self::UnknownRole(id) => {
+                write!(f, "The reward role `{}` no longer exists", id)
+            },
+            Self::Http(e) => write!(f, "Failed to give out a reward: {}", e),
+        }
+    }
+}
+
+/// The result of giving a member their level rewards
+#[derive(Debug, Default)]
+pub struct RewardOutcome {
+    pub granted: Vec<RoleId>,
+    pub errors: Vec<RewardError>,
+}
+
+/// Scale an XP roll by every multiplier that applies to the member and
+/// channel it was earned in. The result is capped at `limit`, so it always
+/// fits in the database
+pub fn apply_multipliers(
+    settings: &GuildSettings,
+    multipliers: &[XpMultiplier],
+    xp: i32,
+    limit: i32,
+    channel: ChannelId,
+    category: Option<ChannelId>,
+    roles: &[RoleId],
+) -> i32 {
+    let multiplier = settings.multiplier_stack().combine(
+        multipliers
+            .iter()
+            .filter(|m| m.applies_to(channel, category, roles))
+            .map(|m| m.multiplier),
+    );
+
+    ((xp as f32 * multiplier).round() as i32).min(limit)
+}
+
+/// Give a member XP, then announce the level-up and hand out reward roles if
+/// it took them to a new level. `source_channel` is where the XP was earned,
+/// if it was earned in a text channel. Otherwise level-ups and reward errors
+/// are posted in the level-up or system channel
+pub async fn grant_xp(
+    ctx: &Context,
+    settings: &GuildSettings,
+    user: &User,
+    xp: i32,
+    source: XpSource,
+    source_channel: Option<ChannelId>,
+) {
+    let guild_id = GuildId(settings.guild_id as u64);
+
+    let (saved, rewards) = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        let saved = match db.add_guild_user_xp(user.id, guild_id, xp, source) {
+            Ok(Some(s)) => s,
+            // blocked members don't earn XP
+            Ok(None) => return,
+            Err(e) => {
+                error!("Failed to grant XP in guild {}: {:?}", guild_id, e);
+                return;
+            },
+        };
+
+        debug!("Saved: {:?}", saved);
+
+        let curve = settings.curve();
+
+        if curve.level_for_xp(saved.xp - xp) == curve.level_for_xp(saved.xp) {
+            return;
+        }
+
+        (saved, db.get_level_rewards(guild_id).unwrap_or_default())
+    };
+
+    let server = guild_id
+        .name(&ctx.cache)
+        .await
+        .unwrap_or_else(|| "this server".to_string());
+    let vars = LevelUpVars::new(user, saved.xp, &settings.curve(), server);
+
+    let content = render_level_up(settings.levelup_message.as_deref(), &vars);
+
+    announce_level_up(ctx, settings, user, source_channel, content).await;
+
+    let outcome =
+        grant_level_rewards(ctx, guild_id, user.id, vars.level, &rewards).await;
+
+    if outcome.errors.is_empty() {
+        return;
+    }
+
+    let warn_channel = match source_channel {
+        Some(c) => Some(c),
+        None => fallback_channel(ctx, settings).await,
+    };
+
+    for e in outcome.errors {
+        warn!("Level rewards in guild {} failed: {}", guild_id, e);
+
+        if let Some(channel_id) = warn_channel {
+            channel_id
+                .say(&ctx.http, format!(":warning: {}", e))
+                .await
+                .ok();
+        }
+    }
+}
+
+/// Send a level-up message wherever the guild wants it. If that isn't
+/// possible (the channel was deleted, the bot can't talk there, or the member
+/// has DMs closed) the message goes to `source_channel` instead. XP earned
+/// outside a text channel falls back to the level-up or system channel, and
+/// then to the member's DMs
+pub async fn announce_level_up(
+    ctx: &Context,
+    settings: &GuildSettings,
+    user: &User,
+    source_channel: Option<ChannelId>,
+    content: String,
+) {
+    match settings.announce_mode() {
+        AnnounceMode::Off => return,
+        AnnounceMode::Current => {},
+        AnnounceMode::Dm => {
+            let sent = user
+                .direct_message(ctx, |m| m.content(&content))
+                .await
+                .is_ok();
+
+            if sent {
+                return;
+            }
+        },
+        AnnounceMode::Channel => {
+            if let Some(channel_id) = settings.levelup_channel {
+                let channel_id = ChannelId(channel_id as u64);
+
+                if can_send_in(ctx, settings, channel_id).await
+                    && send_announcement(ctx, channel_id, user, &content)
+                        .await
+                        .is_ok()
+                {
+                    return;
+                }
+
+                debug!(
+                    "Can't announce level-ups in {} for guild {}, falling back",
+                    channel_id, settings.guild_id
+                );
+            }
+        },
+    }
+
+    let channel_id = match source_channel {
+        Some(c) => Some(c),
+        None => fallback_channel(ctx, settings).await,
+    };
+
+    match channel_id {
+        Some(channel_id) => {
+            send_announcement(ctx, channel_id, user, &content)
+                .await
+                .ok();
+        },
+        None if settings.announce_mode() != AnnounceMode::Dm => {
+            user.direct_message(ctx, |m| m.content(&content)).await.ok();
+        },
+        None => {},
+    }
+}
+
+/// Where to post about XP that wasn't earned in a text channel, like voice
+/// XP: the level-up channel if there is one, otherwise the system channel
+async fn fallback_channel(
+    ctx: &Context,
+    settings: &GuildSettings,
+) -> Option<ChannelId> {
+    let guild_id = GuildId(settings.guild_id as u64);
+    let system_channel = ctx
+        .cache
+        .guild_field(guild_id, |g| g.system_channel_id)
+        .await
+        .flatten();
+
+    let channels = settings
+        .levelup_channel
+        .map(|c| ChannelId(c as u64))
+        .into_iter()
+        .chain(system_channel);
+
+    for channel_id in channels {
+        if can_send_in(ctx, settings, channel_id).await {
+            return Some(channel_id);
+        }
+    }
+
+    None
+}
+
+/// Check that the bot is able to give `role_id` to members
+pub async fn check_reward_role(
+    ctx: &Context,
+    guild: &Guild,
+    role_id: RoleId,
+) -> Result<(), RewardError> {
+    let top_position = bot_top_role_position(ctx, guild).await?;
+
+    check_role_position(guild, role_id, top_position)
+}
+
+/// Give a member every reward role unlocked at or below `level` that they
+/// don't already have
+pub async fn grant_level_rewards(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    level: i32,
+    rewards: &[LevelReward],
+) -> RewardOutcome {
+    let mut outcome = RewardOutcome::default();
+
+    let unlocked = rewards
+        .iter()
+        .filter(|r| r.level <= level)
+        .map(|r| RoleId(r.role_id as u64))
+        .collect::<Vec<RoleId>>();
+
+    if unlocked.is_empty() {
+        return outcome;
+    }
+
+    let guild = match ctx.cache.guild(guild_id).await {
+        Some(g) => g,
+        None => {
+            outcome.errors.push(RewardError::GuildUnavailable);
+            return outcome;
+        },
+    };
+
+    let mut member = match guild.member(ctx, user_id).await {
+        Ok(m) => m,
+        Err(e) => {
+            outcome.errors.push(RewardError::Http(e));
+            return outcome;
+        },
+    };
+
+    let missing = unlocked
+        .into_iter()
+        .filter(|r| !member.roles.contains(r))
+        .collect::<Vec<RoleId>>();
+
+    if missing.is_empty() {
+        return outcome;
+    }
+
+    let top_position = match bot_top_role_position(ctx, &guild).await {
+        Ok(p) => p,
+        Err(e) => {
+            outcome.errors.push(e);
+            return outcome;
+        },
+    };
+
+    let mut to_add = vec![];
+
+    for role_id in missing {
+        match check_role_position(&guild, role_id, top_position) {
+            Ok(_) => to_add.push(role_id),
+            Err(e) => outcome.errors.push(e),
+        }
+    }
+
+    if !to_add.is_empty() {
+        match member.add_roles(&ctx.http, &to_add).await {
+            Ok(_) => outcome.granted = to_add,
+            Err(e) => outcome.errors.push(RewardError::Http(e)),
+        }
+    }
+
+    outcome
+}
+
+/// Take away the reward roles the members in `levels` are no longer a high
+/// enough level for, e.g. after their XP was reset. Members who aren't in the
+/// cache are skipped. Returns how many members lost roles
+pub async fn revoke_level_rewards(
+    ctx: &Context,
+    guild_id: GuildId,
+    levels: &HashMap<UserId, i32>,
+    rewards: &[LevelReward],
+) -> Result<usize, RewardError> {
+    let guild = ctx
+        .cache
+        .guild(guild_id)
+        .await
+        .ok_or(RewardError::GuildUnavailable)?;
+    let top_position = bot_top_role_position(ctx, &guild).await?;
+
+    let mut revoked = 0;
+
+    for (user_id, level) in levels {
+        let member = match guild.members.get(user_id) {
+            Some(m) => m,
+            None => continue,
+        };
+
+        let locked = rewards
+            .iter()
+            .filter(|r| r.level > *level)
+            .map(|r| RoleId(r.role_id as u64))
+            .filter(|r| member.roles.contains(r))
+            .filter(|r| check_role_position(&guild, *r, top_position).is_ok())
+            .collect::<Vec<RoleId>>();
+
+        if locked.is_empty() {
+            continue;
+        }
+
+        match member.clone().remove_roles(&ctx.http, &locked).await {
+            Ok(_) => revoked += 1,
+            Err(e) => warn!(
+                "Failed to revoke level rewards from {} in guild {}: {}",
+                member.user.id, guild_id, e
+            ),
+        }
+    }
+
+    Ok(revoked)
+}
+
+async fn send_announcement(
+    ctx: &Context,
+    channel_id: ChannelId,
+    user: &User,
+    content: &str,
+) -> Result<Message, SerenityError> {
+    channel_id
+        .send_message(&ctx.http, |m| {
+            m.allowed_mentions(|am| am.empty_parse().users(vec![user.id]))
+                .content(content)
+        })
+        .await
+}
+
+/// Check the cache to see if the bot can post in one of the guild's
+/// channels, so a deleted or locked channel doesn't cost a failed request on
+/// every level-up. Channels in other guilds are never used
+async fn can_send_in(
+    ctx: &Context,
+    settings: &GuildSettings,
+    channel_id: ChannelId,
+) -> bool {
+    let channel = match ctx.cache.guild_channel(channel_id).await {
+        Some(c) if c.guild_id.0 as i64 == settings.guild_id => c,
+        _ => return false,
+    };
+
+    let bot_id = ctx.cache.current_user_id().await;
+
+    channel
+        .permissions_for_user(&ctx.cache, bot_id)
+        .await
+        .map(|p| p.read_messages() && p.send_messages())
+        .unwrap_or(false)
+}
+
+fn check_role_position(
+    guild: &Guild,
+    role_id: RoleId,
+    top_position: i64,
+) -> Result<(), RewardError> {
+    match guild.roles.get(&role_id) {
+        Some(role) if role.position >= top_position => {
+            Err(RewardError::RoleTooHigh(role.name.clone()))
+        },
+        Some(_) => Ok(()),
+        None => Err(RewardError::UnknownRole(role_id)),
+    }
+}
+
+/// Get the position of the bot's highest role, making sure it is allowed to
+/// manage roles at all
+async fn bot_top_role_position(
+    ctx: &Context,
+    guild: &Guild,
+) -> Result<i64, RewardError> {
+    let bot_id = ctx.cache.current_user_id().await;
+
+    let permissions = guild
+        .member_permissions(ctx, bot_id)
+        .await
+        .map_err(RewardError::Http)?;
+
+    if !permissions.manage_roles() {
+        return Err(RewardError::MissingPermissions);
+    }
+
+    let bot_member =
+        guild.member(ctx, bot_id).await.map_err(RewardError::Http)?;
+
+    Ok(bot_member
+        .highest_role_info(&ctx.cache)
+        .await
+        .map(|(_, position)| position)
+        .unwrap_or(0))
+}
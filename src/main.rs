@@ -1,20 +1,58 @@
+// diesel 1.x's `table!` and derive macros expand to impls nested in consts
+#![allow(non_local_definitions)]
+
+mod backup;
 mod cmds;
 mod db;
+mod decay;
+mod departed;
+mod export;
 mod hooks;
+mod import;
+mod leaderboard;
+mod levelup;
 pub mod models;
+#[cfg(feature = "images")]
+pub mod render;
 pub mod schema;
 pub mod util;
-
-use std::{collections::HashSet, env, sync::Arc, time::Duration};
+mod voice;
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 #[macro_use]
 extern crate diesel;
 
-use cmds::{meta::*, xp::*};
+use cmds::{
+    backup::*,
+    export::*,
+    filters::*,
+    import::*,
+    levelup::*,
+    meta::*,
+    moderation::*,
+    multipliers::*,
+    privacy::*,
+    profile::*,
+    rewards::*,
+    seasons::*,
+    settings::*,
+    xp::*,
+};
 use db::{postgres::Database, redis::RedisCache};
 use dotenv::dotenv;
 use fluent_templates::static_loader;
 use lru_time_cache::LruCache;
+#[cfg(feature = "images")]
+use render::avatar::{AvatarFetcher, CachedAvatarFetcher, HttpAvatarFetcher};
 use serenity::{
     async_trait,
     client::{
@@ -29,9 +67,18 @@ use serenity::{
 use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 
-pub const MIN_MESSAGE_XP: i32 = 15;
-pub const MAX_MESSAGE_XP: i32 = 25;
-pub const XP_TIMEOUT_SECS: u64 = 60;
+/// The most XP a guild can hand out for a single message
+pub const MESSAGE_XP_LIMIT: i32 = 1000;
+/// The longest cooldown a guild can set between XP grants
+pub const XP_TIMEOUT_LIMIT_SECS: i32 = 60 * 60;
+/// The most XP a guild can hand out per minute in voice
+pub const VOICE_XP_LIMIT: i32 = 1000;
+/// The largest XP multiplier a role or channel can have
+pub const MULTIPLIER_LIMIT: f32 = 10.0;
+/// The longest grace period a guild can give inactive members before decay
+pub const DECAY_GRACE_LIMIT_DAYS: i32 = 365;
+/// The most XP fixed decay can take from a member each day
+pub const DECAY_XP_LIMIT: i32 = 10_000;
 
 static_loader! {
     pub static LOCALES = {
@@ -42,7 +89,6 @@ static_loader! {
     };
 }
 
-
 #[macro_export]
 macro_rules! args {
     ( $($key:expr => $val:expr),* $(,)? ) => {
@@ -68,17 +114,57 @@ macro_rules! args {
     create_guild_cmd,
     prefix_cmd,
     fluent_test_cmd,
+    purge_user_cmd
 )]
 #[description = "Meta commands, idk, nothing too special here"]
 struct MetaCmds;
 
 #[group("XP")]
-#[commands(set_xp_cmd, rank_cmd)]
+#[commands(
+    set_xp_cmd,
+    rank_cmd,
+    history_cmd,
+    rewards_cmd,
+    season_cmd,
+    profile_cmd,
+    global_leaderboard_cmd
+)]
 #[description = "Commands related to the XP leveling system"]
 struct XpCmds;
 
+#[group("Settings")]
+#[commands(settings_cmd, levelup_cmd, noxp_cmd, multiplier_cmd)]
+#[description = "Configure how the bot behaves in this server"]
+struct SettingsCmds;
+
+#[group("Moderation")]
+#[commands(
+    block_cmd,
+    unblock_cmd,
+    blocked_cmd,
+    xp_cmd,
+    import_cmd,
+    export_cmd,
+    backup_cmd,
+    restore_cmd
+)]
+#[description = "Manage who can earn XP in this server"]
+struct ModerationCmds;
+
+#[group("Privacy")]
+#[commands(mydata_cmd, forgetme_cmd)]
+#[description = "See and erase what the bot stores about you"]
+struct PrivacyCmds;
+
 pub struct ShardManagerContainer;
 pub struct MessageXPTimeoutCache;
+pub struct VoiceSessions;
+pub struct LeaderboardPages;
+pub struct PendingGuildResets;
+pub struct PendingSeasonStarts;
+pub struct PendingDeletions;
+#[cfg(feature = "images")]
+pub struct Avatars;
 
 impl TypeMapKey for ShardManagerContainer {
     type Value = Arc<Mutex<ShardManager>>;
@@ -89,10 +175,39 @@ impl TypeMapKey for Database {
 }
 
 impl TypeMapKey for MessageXPTimeoutCache {
-    type Value = Arc<Mutex<LruCache<(UserId, GuildId), i32>>>;
+    type Value = Arc<Mutex<LruCache<(UserId, GuildId), Instant>>>;
+}
+
+impl TypeMapKey for VoiceSessions {
+    type Value = Arc<Mutex<HashMap<(GuildId, UserId), Instant>>>;
+}
+
+impl TypeMapKey for LeaderboardPages {
+    type Value = Arc<Mutex<LruCache<MessageId, leaderboard::LeaderboardPage>>>;
 }
 
-struct Handler;
+impl TypeMapKey for PendingGuildResets {
+    type Value = Arc<Mutex<LruCache<GuildId, UserId>>>;
+}
+
+impl TypeMapKey for PendingSeasonStarts {
+    type Value = Arc<Mutex<LruCache<GuildId, PendingSeasonStart>>>;
+}
+
+impl TypeMapKey for PendingDeletions {
+    type Value = Arc<Mutex<LruCache<UserId, ()>>>;
+}
+
+#[cfg(feature = "images")]
+impl TypeMapKey for Avatars {
+    type Value = Arc<dyn AvatarFetcher>;
+}
+
+struct Handler {
+    tickers_started: AtomicBool,
+    /// How many days to keep the data of guilds the bot was removed from
+    guild_retention_days: i64,
+}
 
 #[async_trait]
 impl EventHandler for Handler {
@@ -104,11 +219,67 @@ impl EventHandler for Handler {
             ready.user.discriminator,
             ready.guilds.len()
         );
+
+        // every shard gets a ready event, but one ticker covers all of them
+        if !self.tickers_started.swap(true, Ordering::SeqCst) {
+            voice::start_voice_ticker(ctx.clone());
+            decay::start_decay_ticker(ctx.clone());
+            departed::start_retention_ticker(ctx, self.guild_retention_days);
+        }
     }
 
     async fn resume(&self, _ctx: Context, _: ResumedEvent) {
         info!("Resumed.");
     }
+
+    async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: bool) {
+        departed::handle_guild_joined(&ctx, guild.id).await;
+        voice::track_guild_voice_states(&ctx, &guild).await;
+    }
+
+    async fn guild_delete(
+        &self,
+        ctx: Context,
+        incomplete: GuildUnavailable,
+        _full: Option<Guild>,
+    ) {
+        departed::handle_guild_left(&ctx, &incomplete).await;
+    }
+
+    async fn guild_member_addition(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        member: Member,
+    ) {
+        departed::handle_member_joined(&ctx, guild_id, &member.user).await;
+    }
+
+    async fn guild_member_removal(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        user: User,
+        _member: Option<Member>,
+    ) {
+        departed::handle_member_left(&ctx, guild_id, &user).await;
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        leaderboard::handle_page_reaction(&ctx, &reaction).await;
+    }
+
+    async fn voice_state_update(
+        &self,
+        ctx: Context,
+        guild_id: Option<GuildId>,
+        _old: Option<VoiceState>,
+        new: VoiceState,
+    ) {
+        if let Some(guild_id) = guild_id.or(new.guild_id) {
+            voice::track_voice_state(&ctx, guild_id, &new).await;
+        }
+    }
 }
 
 #[tokio::main]
@@ -134,6 +305,14 @@ async fn main() {
     let redis_url =
         env::var("REDIS_URL").expect("Expected `REDIS_URL` in the environment");
 
+    let guild_retention_days =
+        match env::var("GUILD_RETENTION_DAYS") {
+            Ok(days) => days.parse::<i64>().ok().filter(|d| *d >= 0).expect(
+                "Expected `GUILD_RETENTION_DAYS` to be a number of days",
+            ),
+            Err(_) => departed::DEFAULT_GUILD_RETENTION_DAYS,
+        };
+
     let http = Http::new_with_token(&token);
 
     let (owners, bot_id) = match http.get_current_application_info().await {
@@ -169,15 +348,23 @@ async fn main() {
         .help(&HELP_CMD)
         .group(&METACMDS_GROUP)
         .group(&XPCMDS_GROUP)
+        .group(&SETTINGSCMDS_GROUP)
+        .group(&MODERATIONCMDS_GROUP)
+        .group(&PRIVACYCMDS_GROUP)
         .normal_message(hooks::normal_message);
 
     let mut client = Client::builder(token)
-        .event_handler(Handler)
+        .event_handler(Handler {
+            tickers_started: AtomicBool::new(false),
+            guild_retention_days,
+        })
         .framework(framework)
         .intents(
             GatewayIntents::GUILDS
                 | GatewayIntents::GUILD_MEMBERS
                 | GatewayIntents::GUILD_MESSAGES
+                | GatewayIntents::GUILD_MESSAGE_REACTIONS
+                | GatewayIntents::GUILD_VOICE_STATES
                 | GatewayIntents::GUILD_PRESENCES, // ugh
         )
         .await
@@ -187,9 +374,9 @@ async fn main() {
     let db = Arc::new(Mutex::new(Database::new(&database_url, redis)));
     let msg_xp_timeout_cache = Arc::new(Mutex::new(LruCache::<
         (UserId, GuildId),
-        i32,
+        Instant,
     >::with_expiry_duration(
-        Duration::from_secs(XP_TIMEOUT_SECS),
+        Duration::from_secs(XP_TIMEOUT_LIMIT_SECS as u64),
     )));
 
     {
@@ -197,6 +384,25 @@ async fn main() {
         data.insert::<ShardManagerContainer>(client.shard_manager.clone());
         data.insert::<Database>(db.clone());
         data.insert::<MessageXPTimeoutCache>(msg_xp_timeout_cache.clone());
+        data.insert::<VoiceSessions>(Arc::new(Mutex::new(HashMap::new())));
+        data.insert::<LeaderboardPages>(Arc::new(Mutex::new(
+            LruCache::with_expiry_duration(leaderboard::PAGE_CONTROLS_TIMEOUT),
+        )));
+        data.insert::<PendingGuildResets>(Arc::new(Mutex::new(
+            LruCache::with_expiry_duration(RESET_CONFIRM_TIMEOUT),
+        )));
+        data.insert::<PendingSeasonStarts>(Arc::new(Mutex::new(
+            LruCache::with_expiry_duration(SEASON_CONFIRM_TIMEOUT),
+        )));
+
+        data.insert::<PendingDeletions>(Arc::new(Mutex::new(
+            LruCache::with_expiry_duration(DELETE_CONFIRM_TIMEOUT),
+        )));
+
+        #[cfg(feature = "images")]
+        data.insert::<Avatars>(Arc::new(CachedAvatarFetcher::new(
+            HttpAvatarFetcher::default(),
+        )));
     }
 
     let shard_manager = client.shard_manager.clone();
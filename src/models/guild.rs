@@ -1,13 +1,171 @@
-use diesel::{Insertable, Queryable};
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::{AsChangeset, Insertable, Queryable};
 use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, RoleId};
 
-use crate::schema::guilds;
+use crate::{
+    models::multiplier::MultiplierStack,
+    schema::guilds,
+    util::curve::LevelCurve,
+};
 
-#[derive(Debug, Queryable, Deserialize, Serialize)]
+#[derive(Debug, Queryable, AsChangeset, Deserialize, Serialize)]
+#[table_name = "guilds"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct Guild {
     pub id: i32,
     pub guild_id: i64,
     pub prefix: String,
+    pub min_xp: i32,
+    pub max_xp: i32,
+    pub xp_timeout_secs: i32,
+    pub levelup_message: Option<String>,
+    pub levelup_mode: String,
+    pub levelup_channel: Option<i64>,
+    pub xp_ignored_channels: Vec<i64>,
+    pub xp_ignored_roles: Vec<i64>,
+    pub xp_allowed_channels: Vec<i64>,
+    pub xp_whitelist_only: bool,
+    pub xp_multiplier_stack: String,
+    pub voice_xp_enabled: bool,
+    pub voice_xp_per_minute: i32,
+    pub voice_exclude_muted: bool,
+    pub voice_require_others: bool,
+    pub voice_exclude_afk: bool,
+    pub level_curve: String,
+    pub decay_enabled: bool,
+    pub decay_grace_days: i32,
+    pub decay_mode: String,
+    pub decay_amount: i32,
+    pub decay_min_level: i32,
+    /// The last day (UTC) decay was applied
+    pub decay_last_run: Option<NaiveDate>,
+    pub departed_mode: String,
+    /// When the bot was removed from the guild
+    pub left_at: Option<DateTime<Utc>>,
+}
+
+impl Guild {
+    pub fn announce_mode(&self) -> AnnounceMode {
+        self.levelup_mode.parse().unwrap_or(AnnounceMode::Current)
+    }
+
+    pub fn multiplier_stack(&self) -> MultiplierStack {
+        self.xp_multiplier_stack
+            .parse()
+            .unwrap_or(MultiplierStack::Multiply)
+    }
+
+    pub fn curve(&self) -> LevelCurve {
+        self.level_curve.parse().unwrap_or_default()
+    }
+
+    pub fn xp_filters(&self) -> XpFilters {
+        XpFilters {
+            xp_ignored_channels: self.xp_ignored_channels.clone(),
+            xp_ignored_roles: self.xp_ignored_roles.clone(),
+            xp_allowed_channels: self.xp_allowed_channels.clone(),
+            xp_whitelist_only: self.xp_whitelist_only,
+        }
+    }
+
+    pub fn voice_settings(&self) -> VoiceSettings {
+        VoiceSettings {
+            voice_xp_enabled: self.voice_xp_enabled,
+            voice_xp_per_minute: self.voice_xp_per_minute,
+            voice_exclude_muted: self.voice_exclude_muted,
+            voice_require_others: self.voice_require_others,
+            voice_exclude_afk: self.voice_exclude_afk,
+        }
+    }
+
+    pub fn decay_mode(&self) -> DecayMode {
+        self.decay_mode.parse().unwrap_or(DecayMode::Percent)
+    }
+
+    pub fn departed_mode(&self) -> DepartedMode {
+        self.departed_mode.parse().unwrap_or(DepartedMode::Keep)
+    }
+
+    pub fn decay_settings(&self) -> DecaySettings {
+        DecaySettings {
+            decay_enabled: self.decay_enabled,
+            decay_grace_days: self.decay_grace_days,
+            decay_mode: self.decay_mode.clone(),
+            decay_amount: self.decay_amount,
+            decay_min_level: self.decay_min_level,
+        }
+    }
+
+    /// Check whether a message sent in `channel` (under `category`) by a member
+    /// with `roles` should earn XP
+    pub fn earns_xp(
+        &self,
+        channel: ChannelId,
+        category: Option<ChannelId>,
+        roles: &[RoleId],
+    ) -> bool {
+        let channels = [Some(channel), category];
+        let listed = |list: &[i64]| {
+            channels
+                .iter()
+                .flatten()
+                .any(|c| list.contains(&(c.0 as i64)))
+        };
+
+        if listed(&self.xp_ignored_channels) {
+            return false;
+        }
+
+        if self.xp_whitelist_only && !listed(&self.xp_allowed_channels) {
+            return false;
+        }
+
+        !roles
+            .iter()
+            .any(|r| self.xp_ignored_roles.contains(&(r.0 as i64)))
+    }
+}
+
+/// The channels and roles that do (or don't) earn XP in a guild
+#[derive(Debug, AsChangeset)]
+#[table_name = "guilds"]
+pub struct XpFilters {
+    pub xp_ignored_channels: Vec<i64>,
+    pub xp_ignored_roles: Vec<i64>,
+    pub xp_allowed_channels: Vec<i64>,
+    pub xp_whitelist_only: bool,
+}
+
+/// The rules for earning XP in a guild's voice channels
+#[derive(Debug, AsChangeset)]
+#[table_name = "guilds"]
+pub struct VoiceSettings {
+    pub voice_xp_enabled: bool,
+    /// XP earned for each full minute spent in a voice channel
+    pub voice_xp_per_minute: i32,
+    /// Don't give XP to members who are muted or deafened
+    pub voice_exclude_muted: bool,
+    /// Don't give XP to members who are alone (not counting bots)
+    pub voice_require_others: bool,
+    /// Don't give XP in the guild's AFK channel
+    pub voice_exclude_afk: bool,
+}
+
+/// How members who stop taking part lose XP in a guild
+#[derive(Debug, AsChangeset)]
+#[table_name = "guilds"]
+pub struct DecaySettings {
+    pub decay_enabled: bool,
+    /// How many days without earning XP before a member starts losing it
+    pub decay_grace_days: i32,
+    pub decay_mode: String,
+    /// The percentage or amount of XP lost each day, depending on the mode
+    pub decay_amount: i32,
+    /// Decay never takes a member below this level
+    pub decay_min_level: i32,
 }
 
 #[derive(Debug, Insertable)]
@@ -16,3 +174,123 @@ pub struct NewGuild {
     pub guild_id: i64,
     pub prefix: String,
 }
+
+/// Where level-up messages are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnounceMode {
+    /// The channel the member leveled up in
+    Current,
+    /// The guild's `levelup_channel`
+    Channel,
+    /// The member's DMs
+    Dm,
+    /// Nowhere
+    Off,
+}
+
+impl AnnounceMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Current => "current",
+            Self::Channel => "channel",
+            Self::Dm => "dm",
+            Self::Off => "off",
+        }
+    }
+}
+
+impl fmt::Display for AnnounceMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AnnounceMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "current" | "same" => Ok(Self::Current),
+            "channel" => Ok(Self::Channel),
+            "dm" | "dms" => Ok(Self::Dm),
+            "off" | "none" | "disabled" => Ok(Self::Off),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How much XP an inactive member loses each day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecayMode {
+    /// A percentage of their XP
+    Percent,
+    /// A fixed amount of XP
+    Fixed,
+}
+
+impl DecayMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Percent => "percent",
+            Self::Fixed => "fixed",
+        }
+    }
+}
+
+impl fmt::Display for DecayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DecayMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "percent" | "%" => Ok(Self::Percent),
+            "fixed" | "xp" => Ok(Self::Fixed),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What happens to a member's XP when they leave a guild
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepartedMode {
+    /// Leave them on the leaderboard
+    Keep,
+    /// Keep their XP, but hide them from the leaderboard until they come back
+    Hide,
+    /// Delete their XP
+    Delete,
+}
+
+impl DepartedMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Keep => "keep",
+            Self::Hide => "hide",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+impl fmt::Display for DepartedMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DepartedMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(Self::Keep),
+            "hide" => Ok(Self::Hide),
+            "delete" | "remove" => Ok(Self::Delete),
+            _ => Err(()),
+        }
+    }
+}
@@ -1,2 +1,7 @@
 pub mod guild;
+pub mod multiplier;
+pub mod reward;
+pub mod season;
 pub mod user;
+pub mod user_settings;
+pub mod xp_event;
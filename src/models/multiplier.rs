@@ -0,0 +1,144 @@
+use std::{fmt, str::FromStr};
+
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, RoleId};
+
+use crate::{schema::xp_multipliers, MULTIPLIER_LIMIT};
+
+/// Scales the XP earned by members with a role or in a channel
+#[derive(Debug, Queryable, Deserialize, Serialize)]
+pub struct XpMultiplier {
+    pub id: i32,
+    pub guild_id: i64,
+    pub target_id: i64,
+    pub kind: String,
+    pub multiplier: f32,
+}
+
+impl XpMultiplier {
+    pub fn target(&self) -> MultiplierTarget {
+        self.kind.parse().unwrap_or(MultiplierTarget::Role)
+    }
+
+    /// Check whether this multiplier applies to a message sent in `channel`
+    /// (under `category`) by a member with `roles`
+    pub fn applies_to(
+        &self,
+        channel: ChannelId,
+        category: Option<ChannelId>,
+        roles: &[RoleId],
+    ) -> bool {
+        let id = self.target_id as u64;
+
+        match self.target() {
+            MultiplierTarget::Role => roles.contains(&RoleId(id)),
+            MultiplierTarget::Channel => {
+                channel.0 == id || category.map(|c| c.0) == Some(id)
+            },
+        }
+    }
+
+    /// Format the target as a mention
+    pub fn mention(&self) -> String {
+        match self.target() {
+            MultiplierTarget::Role => format!("<@&{}>", self.target_id),
+            MultiplierTarget::Channel => format!("<#{}>", self.target_id),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "xp_multipliers"]
+pub struct NewXpMultiplier {
+    pub guild_id: i64,
+    pub target_id: i64,
+    pub kind: String,
+    pub multiplier: f32,
+}
+
+/// What an XP multiplier is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultiplierTarget {
+    Role,
+    /// A channel, or every channel in a category
+    Channel,
+}
+
+impl MultiplierTarget {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Role => "role",
+            Self::Channel => "channel",
+        }
+    }
+}
+
+impl FromStr for MultiplierTarget {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "role" => Ok(Self::Role),
+            "channel" => Ok(Self::Channel),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How multipliers are combined when more than one applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultiplierStack {
+    /// 1.5x and 2x give 3x
+    Multiply,
+    /// 1.5x and 2x give 2.5x
+    Add,
+    /// 1.5x and 2x give 2x
+    Highest,
+}
+
+impl MultiplierStack {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Multiply => "multiply",
+            Self::Add => "add",
+            Self::Highest => "highest",
+        }
+    }
+
+    /// Combine the multipliers that apply to a message into a single factor.
+    /// Stacking can't take it past `MULTIPLIER_LIMIT`
+    pub fn combine(&self, multipliers: impl Iterator<Item = f32>) -> f32 {
+        let mut multipliers = multipliers.peekable();
+
+        if multipliers.peek().is_none() {
+            return 1.0;
+        }
+
+        match self {
+            Self::Multiply => multipliers.product(),
+            Self::Add => 1.0 + multipliers.map(|m| m - 1.0).sum::<f32>(),
+            Self::Highest => multipliers.fold(f32::MIN, f32::max),
+        }
+        .clamp(0.0, MULTIPLIER_LIMIT)
+    }
+}
+
+impl fmt::Display for MultiplierStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MultiplierStack {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "multiply" | "product" => Ok(Self::Multiply),
+            "add" | "sum" => Ok(Self::Add),
+            "highest" | "max" => Ok(Self::Highest),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::level_rewards;
+
+/// A role given to members once they reach a level
+#[derive(Debug, Queryable, Deserialize, Serialize)]
+pub struct LevelReward {
+    pub id: i32,
+    pub guild_id: i64,
+    pub role_id: i64,
+    pub level: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "level_rewards"]
+pub struct NewLevelReward {
+    pub guild_id: i64,
+    pub role_id: i64,
+    pub level: i32,
+}
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::seasons;
+
+/// A finished leaderboard season. The current season isn't stored until it
+/// ends
+#[derive(Debug, Queryable, Deserialize, Serialize)]
+pub struct Season {
+    pub id: i32,
+    pub guild_id: i64,
+    /// Counts up from 1 in each guild
+    pub number: i32,
+    /// When the previous season ended, or `None` for the guild's first
+    pub started_at: Option<DateTime<Utc>>,
+    pub ended_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "seasons"]
+pub struct NewSeason {
+    pub guild_id: i64,
+    pub number: i32,
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+/// A member's final place on the leaderboard when a season ended
+#[derive(Debug, Queryable, Deserialize, Serialize)]
+pub struct SeasonStanding {
+    pub season_id: i32,
+    pub position: i32,
+    pub user_id: i64,
+    pub xp: i32,
+}
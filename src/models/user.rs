@@ -1,16 +1,59 @@
-use diesel::{Insertable, Queryable};
+use chrono::{DateTime, Utc};
+use diesel::{
+    sql_types::{BigInt, Nullable},
+    Insertable,
+    Queryable,
+    QueryableByName,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::schema::users;
 
 // TODO: rename GuildUser?
-#[derive(Debug, Queryable, Deserialize, Serialize)]
+#[derive(Debug, Queryable, QueryableByName, Deserialize, Serialize)]
+#[table_name = "users"]
 pub struct User {
     pub id: i32,
     pub user_id: i64,
     pub guild_id: i64,
     pub xp: i32,
     pub blocked: bool,
+    /// When the user last earned XP by taking part
+    pub last_xp_at: DateTime<Utc>,
+    /// When the member left, if the guild hides members who leave from the
+    /// leaderboard
+    pub left_at: Option<DateTime<Utc>>,
+}
+
+/// A user with their position on the guild's leaderboard, or `None` if
+/// they're blocked or hidden
+#[derive(Debug, QueryableByName)]
+pub struct RankedUser {
+    #[diesel(embed)]
+    pub user: User,
+    #[sql_type = "Nullable<BigInt>"]
+    pub rank: Option<i64>,
+}
+
+/// A user's XP summed across every guild they aren't blocked in
+#[derive(Debug, QueryableByName)]
+pub struct GlobalUser {
+    #[sql_type = "BigInt"]
+    pub user_id: i64,
+    #[sql_type = "BigInt"]
+    pub xp: i64,
+    /// How many guilds the XP came from
+    #[sql_type = "BigInt"]
+    pub guilds: i64,
+}
+
+/// The XP a user earned in a guild over a range of days
+#[derive(Debug, QueryableByName)]
+pub struct WindowUser {
+    #[sql_type = "BigInt"]
+    pub user_id: i64,
+    #[sql_type = "BigInt"]
+    pub xp: i64,
 }
 
 #[derive(Debug, Insertable)]
@@ -0,0 +1,14 @@
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::user_settings;
+
+/// A user's preferences, shared across every guild
+#[derive(Debug, Default, Queryable, Insertable, Deserialize, Serialize)]
+#[table_name = "user_settings"]
+pub struct UserSettings {
+    pub user_id: i64,
+    /// Hide the list of guilds on the user's global profile from everyone
+    /// else
+    pub hide_guilds: bool,
+}
@@ -0,0 +1,96 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::Queryable;
+use serde::Serialize;
+
+/// One change to a member's XP, as recorded in the ledger
+#[derive(Debug, Queryable, Serialize)]
+pub struct XpEvent {
+    pub id: i64,
+    pub guild_id: i64,
+    pub user_id: i64,
+    /// How much the XP changed by, which is negative if it went down
+    pub xp: i32,
+    pub created_at: DateTime<Utc>,
+    pub source: String,
+    /// Who made the change, for changes made by a person
+    pub actor_id: Option<i64>,
+}
+
+/// The XP a member earned in a guild on one day (UTC)
+#[derive(Debug, Queryable, Serialize)]
+pub struct XpDay {
+    pub guild_id: i64,
+    pub day: NaiveDate,
+    pub user_id: i64,
+    pub xp: i64,
+}
+
+impl XpEvent {
+    pub fn source(&self) -> XpSource {
+        self.source.parse().unwrap_or(XpSource::Admin)
+    }
+}
+
+/// Where a change to a member's XP came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XpSource {
+    Message,
+    Voice,
+    /// Changed by a moderator with a command
+    Admin,
+    Import,
+    Decay,
+    /// Reset when a new leaderboard season started
+    Season,
+    /// Replaced when the guild was restored from a backup
+    Restore,
+    /// Removed when the member left a guild that deletes their XP
+    Left,
+}
+
+impl XpSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Message => "message",
+            Self::Voice => "voice",
+            Self::Admin => "admin",
+            Self::Import => "import",
+            Self::Decay => "decay",
+            Self::Season => "season",
+            Self::Restore => "restore",
+            Self::Left => "left",
+        }
+    }
+
+    /// Whether the XP was earned by taking part, and so counts towards the
+    /// daily, weekly and monthly leaderboards
+    pub fn is_earned(&self) -> bool {
+        matches!(self, Self::Message | Self::Voice)
+    }
+}
+
+impl FromStr for XpSource {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "message" => Ok(Self::Message),
+            "voice" => Ok(Self::Voice),
+            "admin" => Ok(Self::Admin),
+            "import" => Ok(Self::Import),
+            "decay" => Ok(Self::Decay),
+            "season" => Ok(Self::Season),
+            "restore" => Ok(Self::Restore),
+            "left" => Ok(Self::Left),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for XpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
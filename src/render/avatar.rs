@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use image::{imageops::FilterType, RgbaImage};
+use lru_time_cache::LruCache;
+use serenity::{async_trait, model::user::User, prelude::Mutex};
+
+/// The size avatars are downloaded and cached at
+pub const AVATAR_FETCH_SIZE: u32 = 256;
+/// How many avatars are kept in memory
+const AVATAR_CACHE_CAPACITY: usize = 500;
+/// How long an avatar is kept in memory. A changed avatar has a new URL, so
+/// this only bounds memory use
+const AVATAR_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Where avatar images come from. The renderers only see this trait, so they
+/// can be given fixture avatars instead of downloading them
+#[async_trait]
+pub trait AvatarFetcher: Send + Sync {
+    /// Get the image at an avatar URL, or `None` if it couldn't be fetched
+    async fn fetch(&self, url: &str) -> Option<RgbaImage>;
+}
+
+/// Downloads avatars from Discord's CDN
+#[derive(Default)]
+pub struct HttpAvatarFetcher {
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl AvatarFetcher for HttpAvatarFetcher {
+    async fn fetch(&self, url: &str) -> Option<RgbaImage> {
+        let res = self.client.get(url).send().await.ok()?;
+        let bytes = res.error_for_status().ok()?.bytes().await.ok()?;
+
+        // decoding and resizing would hold up every other event on this
+        // worker, so they're done on the blocking pool
+        tokio::task::spawn_blocking(move || decode_avatar(&bytes))
+            .await
+            .ok()?
+    }
+}
+
+/// Decode a downloaded avatar, shrinking it to `AVATAR_FETCH_SIZE` if it's
+/// bigger
+fn decode_avatar(bytes: &[u8]) -> Option<RgbaImage> {
+    let img = image::load_from_memory(bytes).ok()?.to_rgba8();
+
+    if img.width() > AVATAR_FETCH_SIZE || img.height() > AVATAR_FETCH_SIZE {
+        Some(image::imageops::resize(
+            &img,
+            AVATAR_FETCH_SIZE,
+            AVATAR_FETCH_SIZE,
+            FilterType::Triangle,
+        ))
+    } else {
+        Some(img)
+    }
+}
+
+/// Keeps recently used avatars in memory in front of another fetcher.
+/// Failed fetches aren't cached, so they're retried next time
+pub struct CachedAvatarFetcher<F> {
+    inner: F,
+    cache: Mutex<LruCache<String, RgbaImage>>,
+}
+
+impl<F: AvatarFetcher> CachedAvatarFetcher<F> {
+    pub fn new(inner: F) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(LruCache::with_expiry_duration_and_capacity(
+                AVATAR_CACHE_TTL,
+                AVATAR_CACHE_CAPACITY,
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl<F: AvatarFetcher> AvatarFetcher for CachedAvatarFetcher<F> {
+    async fn fetch(&self, url: &str) -> Option<RgbaImage> {
+        if let Some(img) = self.cache.lock().await.get(url) {
+            return Some(img.clone());
+        }
+
+        let img = self.inner.fetch(url).await?;
+        self.cache.lock().await.insert(url.to_string(), img.clone());
+
+        Some(img)
+    }
+}
+
+/// The URL of a user's avatar as a still PNG
+pub fn avatar_url(user: &User) -> String {
+    match &user.avatar {
+        Some(hash) => format!(
+            "https://cdn.discordapp.com/avatars/{}/{}.png?size={}",
+            user.id, hash, AVATAR_FETCH_SIZE
+        ),
+        None => user.default_avatar_url(),
+    }
+}
+
+#[cfg(all(test, feature = "images"))]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::render::testing::FixtureAvatarFetcher;
+
+    #[tokio::test]
+    async fn cached_fetcher_fetches_each_url_once() {
+        let fetcher = CachedAvatarFetcher::new(FixtureAvatarFetcher::default());
+
+        for _ in 0..3 {
+            assert!(fetcher.fetch("https://example.com/a.png").await.is_some());
+            assert!(fetcher.fetch("https://example.com/b.png").await.is_some());
+        }
+
+        assert_eq!(fetcher.inner.fetches.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,199 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{
+    imageops::{self, FilterType},
+    Pixel,
+    Rgba,
+    RgbaImage,
+};
+
+/// Blend `color` over the pixel at (`x`, `y`), scaled by how much of the
+/// pixel the shape covers. Pixels outside the image are ignored
+pub fn blend(
+    img: &mut RgbaImage,
+    x: i32,
+    y: i32,
+    color: Rgba<u8>,
+    coverage: f32,
+) {
+    if x < 0 || y < 0 || x as u32 >= img.width() || y as u32 >= img.height() {
+        return;
+    }
+
+    let coverage = coverage.clamp(0.0, 1.0);
+
+    if coverage == 0.0 {
+        return;
+    }
+
+    let mut color = color;
+    color[3] = (color[3] as f32 * coverage).round() as u8;
+
+    img.get_pixel_mut(x as u32, y as u32).blend(&color);
+}
+
+/// Fill a rectangle with rounded corners. A `radius` of half the height gives
+/// pill-shaped ends
+pub fn fill_rounded_rect(
+    img: &mut RgbaImage,
+    (x, y): (i32, i32),
+    (w, h): (u32, u32),
+    radius: f32,
+    color: Rgba<u8>,
+) {
+    let radius = radius.min(w as f32 / 2.0).min(h as f32 / 2.0);
+    let (left, top) = (x as f32 + radius, y as f32 + radius);
+    let (right, bottom) = (
+        (x + w as i32) as f32 - radius,
+        (y + h as i32) as f32 - radius,
+    );
+
+    for py in y..y + h as i32 {
+        for px in x..x + w as i32 {
+            let (fx, fy) = (px as f32 + 0.5, py as f32 + 0.5);
+
+            // distance from the pixel to the rectangle the corners are
+            // rounded around, which is 0 everywhere except the corners
+            let dx = fx - fx.clamp(left, right);
+            let dy = fy - fy.clamp(top, bottom);
+            let d = (dx * dx + dy * dy).sqrt();
+
+            let coverage = if d == 0.0 { 1.0 } else { radius + 0.5 - d };
+
+            blend(img, px, py, color, coverage);
+        }
+    }
+}
+
+/// Fill a circle of diameter `size` with its top left corner at (`x`, `y`)
+pub fn fill_circle(
+    img: &mut RgbaImage,
+    (x, y): (i32, i32),
+    size: u32,
+    color: Rgba<u8>,
+) {
+    fill_rounded_rect(img, (x, y), (size, size), size as f32 / 2.0, color);
+}
+
+/// Draw `src` scaled to a circle of diameter `size` with its top left corner
+/// at (`x`, `y`)
+pub fn draw_circle_image(
+    img: &mut RgbaImage,
+    src: &RgbaImage,
+    (x, y): (i32, i32),
+    size: u32,
+) {
+    let src = imageops::resize(src, size, size, FilterType::Triangle);
+    let r = size as f32 / 2.0;
+
+    for (sx, sy, pixel) in src.enumerate_pixels() {
+        let dx = sx as f32 + 0.5 - r;
+        let dy = sy as f32 + 0.5 - r;
+        let d = (dx * dx + dy * dy).sqrt();
+
+        blend(img, x + sx as i32, y + sy as i32, *pixel, r + 0.5 - d);
+    }
+}
+
+/// How wide `text` is when drawn at `size` pixels
+pub fn text_width(font: &FontRef, size: f32, text: &str) -> f32 {
+    let font = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut last = None;
+
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+
+        if let Some(last) = last {
+            width += font.kern(last, id);
+        }
+
+        width += font.h_advance(id);
+        last = Some(id);
+    }
+
+    width
+}
+
+/// Shorten `text` with an ellipsis until it fits in `max_width` pixels
+pub fn fit_text(
+    font: &FontRef,
+    size: f32,
+    text: &str,
+    max_width: f32,
+) -> String {
+    if text_width(font, size, text) <= max_width {
+        return text.to_string();
+    }
+
+    let mut chars = text.chars().collect::<Vec<char>>();
+
+    while !chars.is_empty() {
+        chars.pop();
+
+        let shortened =
+            format!("{}…", chars.iter().collect::<String>().trim_end());
+
+        if text_width(font, size, &shortened) <= max_width {
+            return shortened;
+        }
+    }
+
+    "…".to_string()
+}
+
+/// Draw `text` at `size` pixels with its baseline starting at (`x`, `y`)
+pub fn draw_text(
+    img: &mut RgbaImage,
+    font: &FontRef,
+    size: f32,
+    (x, y): (f32, f32),
+    color: Rgba<u8>,
+    text: &str,
+) {
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let mut caret = x;
+    let mut last = None;
+
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+
+        if let Some(last) = last {
+            caret += scaled.kern(last, id);
+        }
+
+        let glyph = id.with_scale_and_position(scale, point(caret, y));
+        caret += scaled.h_advance(id);
+        last = Some(id);
+
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+
+            outlined.draw(|gx, gy, coverage| {
+                blend(
+                    img,
+                    bounds.min.x as i32 + gx as i32,
+                    bounds.min.y as i32 + gy as i32,
+                    color,
+                    coverage,
+                );
+            });
+        }
+    }
+}
+
+/// Draw `text` so that it ends at `right`, with its baseline at `y`. Returns
+/// where the text starts
+pub fn draw_text_right(
+    img: &mut RgbaImage,
+    font: &FontRef,
+    size: f32,
+    (right, y): (f32, f32),
+    color: Rgba<u8>,
+    text: &str,
+) -> f32 {
+    let x = right - text_width(font, size, text);
+    draw_text(img, font, size, (x, y), color, text);
+
+    x
+}
@@ -0,0 +1,194 @@
+use image::{Rgba, RgbaImage};
+
+use super::{
+    compact_number,
+    draw::{
+        draw_circle_image,
+        draw_text,
+        draw_text_right,
+        fill_circle,
+        fill_rounded_rect,
+        fit_text,
+    },
+    font_bold,
+    font_regular,
+};
+use crate::util::curve::LevelProgress;
+
+pub const BOARD_WIDTH: u32 = 934;
+/// The most members the image leaderboard can show
+pub const LEADERBOARD_IMAGE_LIMIT: i64 = 25;
+
+const HEADER_HEIGHT: u32 = 90;
+const ROW_HEIGHT: u32 = 96;
+const MARGIN: i32 = 20;
+
+const BACKGROUND: Rgba<u8> = Rgba([35, 39, 42, 255]);
+const ROW: Rgba<u8> = Rgba([9, 10, 11, 180]);
+const TEXT: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT_MUTED: Rgba<u8> = Rgba([127, 131, 132, 255]);
+const ACCENT: Rgba<u8> = Rgba([98, 211, 245, 255]);
+const BAR_BACKGROUND: Rgba<u8> = Rgba([72, 75, 78, 255]);
+/// Gold, silver and bronze for the top three
+const PODIUM: [Rgba<u8>; 3] = [
+    Rgba([255, 201, 54, 255]),
+    Rgba([196, 204, 212, 255]),
+    Rgba([205, 127, 50, 255]),
+];
+
+const AVATAR_SIZE: u32 = 64;
+const RIGHT_EDGE: f32 = (BOARD_WIDTH as i32 - MARGIN - 20) as f32;
+
+/// One member on the image leaderboard
+pub struct LeaderboardRow<'a> {
+    pub rank: i64,
+    pub username: &'a str,
+    /// `None` draws a placeholder circle
+    pub avatar: Option<&'a RgbaImage>,
+    pub progress: LevelProgress,
+}
+
+/// Draw the leaderboard as a stack of cards, one per member
+pub fn render_leaderboard(title: &str, rows: &[LeaderboardRow]) -> RgbaImage {
+    let regular = font_regular();
+    let bold = font_bold();
+
+    let height = HEADER_HEIGHT + ROW_HEIGHT * rows.len() as u32 + MARGIN as u32;
+    let mut img = RgbaImage::from_pixel(BOARD_WIDTH, height, BACKGROUND);
+
+    let title = fit_text(&bold, 40.0, title, RIGHT_EDGE - 40.0);
+    draw_text(&mut img, &bold, 40.0, (40.0, 62.0), TEXT, &title);
+
+    for (i, row) in rows.iter().enumerate() {
+        let top = (HEADER_HEIGHT + ROW_HEIGHT * i as u32) as i32;
+        draw_row(&mut img, &regular, &bold, top, row);
+    }
+
+    img
+}
+
+fn draw_row(
+    img: &mut RgbaImage,
+    regular: &ab_glyph::FontRef,
+    bold: &ab_glyph::FontRef,
+    top: i32,
+    row: &LeaderboardRow,
+) {
+    let card_size = (BOARD_WIDTH - MARGIN as u32 * 2, ROW_HEIGHT - 10);
+    fill_rounded_rect(img, (MARGIN, top), card_size, 12.0, ROW);
+
+    // rank, centered in the space before the avatar
+    let rank_color = match row.rank {
+        1..=3 => PODIUM[row.rank as usize - 1],
+        _ => TEXT,
+    };
+    let rank = format!("#{}", row.rank);
+    let rank_size = if rank.len() > 4 { 22.0 } else { 30.0 };
+    draw_text_right(
+        img,
+        bold,
+        rank_size,
+        (118.0, top as f32 + 55.0),
+        rank_color,
+        &rank,
+    );
+
+    let avatar_pos =
+        (134, top + (ROW_HEIGHT as i32 - 10 - AVATAR_SIZE as i32) / 2);
+    match row.avatar {
+        Some(avatar) => draw_circle_image(img, avatar, avatar_pos, AVATAR_SIZE),
+        None => fill_circle(img, avatar_pos, AVATAR_SIZE, BAR_BACKGROUND),
+    }
+
+    // level and XP on the right, then the name in whatever space is left
+    let text_y = top as f32 + 40.0;
+    let xp = format!("{} XP", compact_number(row.progress.xp));
+    let x = draw_text_right(
+        img,
+        regular,
+        22.0,
+        (RIGHT_EDGE, text_y),
+        TEXT_MUTED,
+        &xp,
+    );
+    let level = row.progress.level.to_string();
+    let x =
+        draw_text_right(img, bold, 26.0, (x - 20.0, text_y), ACCENT, &level);
+    let x =
+        draw_text_right(img, regular, 18.0, (x - 6.0, text_y), ACCENT, "LEVEL");
+
+    let name_x = 216.0;
+    let name = fit_text(bold, 26.0, row.username, x - name_x - 20.0);
+    draw_text(img, bold, 26.0, (name_x, text_y), TEXT, &name);
+
+    // progress through the current level
+    let bar_pos = (name_x as i32, top + 54);
+    let bar_size = ((RIGHT_EDGE - name_x) as u32, 14);
+    fill_rounded_rect(img, bar_pos, bar_size, 7.0, BAR_BACKGROUND);
+
+    let filled = match row.progress.level_size() {
+        Some(size) => {
+            (bar_size.0 as i64 * row.progress.xp_into_level / size) as u32
+        },
+        None => bar_size.0,
+    };
+
+    if filled > 0 {
+        let filled = filled.max(bar_size.1);
+        fill_rounded_rect(img, bar_pos, (filled, bar_size.1), 7.0, ACCENT);
+    }
+}
+
+#[cfg(all(test, feature = "images"))]
+mod tests {
+    use super::*;
+    use crate::{
+        render::{
+            avatar::{AvatarFetcher, CachedAvatarFetcher},
+            testing::{assert_golden, FixtureAvatarFetcher},
+        },
+        util::curve::LevelCurve,
+    };
+
+    #[tokio::test]
+    async fn leaderboard_matches_golden() {
+        let fetcher = CachedAvatarFetcher::new(FixtureAvatarFetcher::default());
+        let members = [
+            ("Ferris", 48_213, true),
+            ("Corro", 12_004, true),
+            ("A name far too long to fit on the leaderboard", 3_150, true),
+            ("No avatar", 475, false),
+            ("Newcomer", 12, true),
+        ];
+
+        let mut avatars = Vec::with_capacity(members.len());
+
+        for (name, _, has_avatar) in &members {
+            avatars.push(
+                if *has_avatar {
+                    fetcher
+                        .fetch(&format!("https://example.com/{}", name))
+                        .await
+                } else {
+                    None
+                },
+            );
+        }
+
+        let rows = members
+            .iter()
+            .zip(&avatars)
+            .enumerate()
+            .map(|(i, ((name, xp, _), avatar))| LeaderboardRow {
+                rank: i as i64 + 1,
+                username: name,
+                avatar: avatar.as_ref(),
+                progress: LevelCurve::Quadratic.progress(*xp),
+            })
+            .collect::<Vec<LeaderboardRow>>();
+
+        let board = render_leaderboard("Top members in Rustaceans", &rows);
+
+        assert_golden("leaderboard.png", &board);
+    }
+}
@@ -0,0 +1,46 @@
+//! Images drawn by the bot, like rank cards. Everything here is drawn on the
+//! CPU so it works on headless servers
+
+pub mod avatar;
+pub mod draw;
+pub mod leaderboard;
+pub mod rank_card;
+#[cfg(test)]
+pub mod testing;
+
+use std::io::Cursor;
+
+use ab_glyph::FontRef;
+use image::{DynamicImage, ImageOutputFormat, RgbaImage};
+
+const FONT_REGULAR: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+const FONT_BOLD: &[u8] =
+    include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf");
+
+pub fn font_regular() -> FontRef<'static> {
+    FontRef::try_from_slice(FONT_REGULAR).expect("Bundled font is invalid")
+}
+
+pub fn font_bold() -> FontRef<'static> {
+    FontRef::try_from_slice(FONT_BOLD).expect("Bundled font is invalid")
+}
+
+/// Encode an image as a PNG to attach to a message
+pub fn encode_png(img: RgbaImage) -> Vec<u8> {
+    let mut png = Cursor::new(Vec::new());
+
+    DynamicImage::ImageRgba8(img)
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .expect("Encoding a PNG in memory can't fail");
+
+    png.into_inner()
+}
+
+/// Shorten a number the way Discord bots usually do: 950, 1.2K, 3.4M
+pub fn compact_number(n: i64) -> String {
+    match n.abs() {
+        0..=999 => n.to_string(),
+        1_000..=999_999 => format!("{:.1}K", n as f64 / 1_000.0),
+        _ => format!("{:.1}M", n as f64 / 1_000_000.0),
+    }
+}
@@ -0,0 +1,223 @@
+use image::{Rgba, RgbaImage};
+
+use super::{
+    compact_number,
+    draw::{
+        draw_circle_image,
+        draw_text,
+        draw_text_right,
+        fill_circle,
+        fill_rounded_rect,
+        fit_text,
+        text_width,
+    },
+    font_bold,
+    font_regular,
+};
+use crate::util::curve::LevelProgress;
+
+pub const CARD_WIDTH: u32 = 934;
+pub const CARD_HEIGHT: u32 = 282;
+
+const BACKGROUND: Rgba<u8> = Rgba([35, 39, 42, 255]);
+const PANEL: Rgba<u8> = Rgba([9, 10, 11, 180]);
+const TEXT: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT_MUTED: Rgba<u8> = Rgba([127, 131, 132, 255]);
+const ACCENT: Rgba<u8> = Rgba([98, 211, 245, 255]);
+const BAR_BACKGROUND: Rgba<u8> = Rgba([72, 75, 78, 255]);
+
+const AVATAR_POS: (i32, i32) = (50, 51);
+const AVATAR_SIZE: u32 = 180;
+const BAR_POS: (i32, i32) = (260, 185);
+const BAR_SIZE: (u32, u32) = (620, 40);
+/// Where the text on the right of the card ends
+const RIGHT_EDGE: f32 = 880.0;
+
+/// Everything shown on a member's rank card
+pub struct RankCard<'a> {
+    pub username: &'a str,
+    pub discriminator: u16,
+    /// `None` draws a placeholder circle
+    pub avatar: Option<&'a RgbaImage>,
+    /// The member's position on the leaderboard and how many members are on
+    /// it, if they're on it
+    pub rank: Option<(i64, i64)>,
+    pub progress: LevelProgress,
+}
+
+/// Draw a rank card like MEE6's
+pub fn render_rank_card(card: &RankCard) -> RgbaImage {
+    let regular = font_regular();
+    let bold = font_bold();
+
+    let mut img = RgbaImage::from_pixel(CARD_WIDTH, CARD_HEIGHT, BACKGROUND);
+    fill_rounded_rect(
+        &mut img,
+        (20, 20),
+        (CARD_WIDTH - 40, CARD_HEIGHT - 40),
+        12.0,
+        PANEL,
+    );
+
+    match card.avatar {
+        Some(avatar) => {
+            draw_circle_image(&mut img, avatar, AVATAR_POS, AVATAR_SIZE)
+        },
+        None => fill_circle(&mut img, AVATAR_POS, AVATAR_SIZE, BAR_BACKGROUND),
+    }
+
+    // level and rank, right to left
+    let level = card.progress.level.to_string();
+    let x = draw_text_right(
+        &mut img,
+        &bold,
+        60.0,
+        (RIGHT_EDGE, 95.0),
+        ACCENT,
+        &level,
+    );
+    let x = draw_text_right(
+        &mut img,
+        &regular,
+        24.0,
+        (x - 8.0, 95.0),
+        ACCENT,
+        "LEVEL",
+    );
+
+    if let Some((rank, total)) = card.rank {
+        let total = format!("of {}", compact_number(total));
+        let x = draw_text_right(
+            &mut img,
+            &regular,
+            24.0,
+            (x - 30.0, 95.0),
+            TEXT_MUTED,
+            &total,
+        );
+
+        let rank = format!("#{}", rank);
+        let x = draw_text_right(
+            &mut img,
+            &bold,
+            60.0,
+            (x - 8.0, 95.0),
+            TEXT,
+            &rank,
+        );
+        draw_text_right(
+            &mut img,
+            &regular,
+            24.0,
+            (x - 8.0, 95.0),
+            TEXT,
+            "RANK",
+        );
+    }
+
+    // XP numbers, right to left, then the name in whatever space is left
+    let x = match (card.progress.xp_into_level, card.progress.level_size()) {
+        (into, Some(size)) => {
+            let needed = format!(" / {} XP", compact_number(size));
+            let x = draw_text_right(
+                &mut img,
+                &regular,
+                26.0,
+                (RIGHT_EDGE, 165.0),
+                TEXT_MUTED,
+                &needed,
+            );
+            draw_text_right(
+                &mut img,
+                &bold,
+                26.0,
+                (x, 165.0),
+                TEXT,
+                &compact_number(into),
+            )
+        },
+        (_, None) => {
+            let total = format!("{} XP", compact_number(card.progress.xp));
+            draw_text_right(
+                &mut img,
+                &bold,
+                26.0,
+                (RIGHT_EDGE, 165.0),
+                TEXT,
+                &total,
+            )
+        },
+    };
+
+    let name_x = BAR_POS.0 as f32 + 10.0;
+    let discriminator = format!("#{:04}", card.discriminator);
+    let name_space =
+        x - name_x - 20.0 - text_width(&regular, 26.0, &discriminator);
+    let name = fit_text(&bold, 36.0, card.username, name_space);
+
+    draw_text(&mut img, &bold, 36.0, (name_x, 165.0), TEXT, &name);
+    draw_text(
+        &mut img,
+        &regular,
+        26.0,
+        (name_x + text_width(&bold, 36.0, &name) + 6.0, 165.0),
+        TEXT_MUTED,
+        &discriminator,
+    );
+
+    // progress bar
+    let (bar_w, bar_h) = BAR_SIZE;
+    let radius = bar_h as f32 / 2.0;
+    fill_rounded_rect(&mut img, BAR_POS, BAR_SIZE, radius, BAR_BACKGROUND);
+
+    let filled = match card.progress.level_size() {
+        Some(size) => {
+            (bar_w as i64 * card.progress.xp_into_level / size) as u32
+        },
+        None => bar_w,
+    };
+
+    if filled > 0 {
+        // short bars still get both rounded ends
+        let filled = filled.max(bar_h);
+        fill_rounded_rect(&mut img, BAR_POS, (filled, bar_h), radius, ACCENT);
+    }
+
+    img
+}
+
+#[cfg(all(test, feature = "images"))]
+mod tests {
+    use super::*;
+    use crate::{
+        render::testing::{assert_golden, fixture_avatar},
+        util::curve::LevelCurve,
+    };
+
+    #[test]
+    fn rank_card_matches_golden() {
+        let avatar = fixture_avatar();
+        let card = render_rank_card(&RankCard {
+            username: "Ferris",
+            discriminator: 42,
+            avatar: Some(&avatar),
+            rank: Some((3, 1250)),
+            progress: LevelCurve::Quadratic.progress(1337),
+        });
+
+        assert_golden("rank_card.png", &card);
+    }
+
+    #[test]
+    fn unranked_card_at_top_of_curve_matches_golden() {
+        let card = render_rank_card(&RankCard {
+            username: "A very long username that has to be shortened",
+            discriminator: 1,
+            avatar: None,
+            rank: None,
+            progress: LevelCurve::Table(vec![100, 200]).progress(4321),
+        });
+
+        assert_golden("rank_card_unranked.png", &card);
+    }
+}
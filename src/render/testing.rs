@@ -0,0 +1,80 @@
+//! Fixtures for testing the renderers without Discord. Golden images are
+//! compared pixel by pixel, and can be rewritten after an intended change to
+//! the drawing code by running the tests with `UPDATE_GOLDEN=1`
+
+use std::{
+    env,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use image::RgbaImage;
+use serenity::async_trait;
+
+use super::avatar::AvatarFetcher;
+
+/// How far a channel can be from the golden image's, to allow for floating
+/// point differences between platforms
+const CHANNEL_TOLERANCE: u8 = 2;
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// The avatar the renderers are tested with
+pub fn fixture_avatar() -> RgbaImage {
+    image::open(fixture_path("avatar.png"))
+        .expect("Failed to open the fixture avatar")
+        .to_rgba8()
+}
+
+/// Check an image matches the golden image `name`, or save it as the golden
+/// image if `UPDATE_GOLDEN` is set
+pub fn assert_golden(name: &str, img: &RgbaImage) {
+    let path = fixture_path(name);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        img.save(&path).expect("Failed to save the golden image");
+        return;
+    }
+
+    let golden = image::open(&path)
+        .unwrap_or_else(|e| panic!("Failed to open {}: {}", name, e))
+        .to_rgba8();
+
+    assert_eq!(
+        img.dimensions(),
+        golden.dimensions(),
+        "{} is the wrong size",
+        name
+    );
+
+    let wrong = img
+        .pixels()
+        .zip(golden.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0.iter())
+                .any(|(a, b)| a.max(b) - a.min(b) > CHANNEL_TOLERANCE)
+        })
+        .count();
+
+    assert_eq!(wrong, 0, "{} pixels don't match {}", wrong, name);
+}
+
+/// Hands out the fixture avatar for every URL, counting how often it's asked
+#[derive(Default)]
+pub struct FixtureAvatarFetcher {
+    pub fetches: AtomicUsize,
+}
+
+#[async_trait]
+impl AvatarFetcher for FixtureAvatarFetcher {
+    async fn fetch(&self, _url: &str) -> Option<RgbaImage> {
+        self.fetches.fetch_add(1, Ordering::SeqCst);
+
+        Some(fixture_avatar())
+    }
+}
@@ -3,6 +3,66 @@ table! {
         id -> Int4,
         guild_id -> Int8,
         prefix -> Varchar,
+        min_xp -> Int4,
+        max_xp -> Int4,
+        xp_timeout_secs -> Int4,
+        levelup_message -> Nullable<Text>,
+        levelup_mode -> Varchar,
+        levelup_channel -> Nullable<Int8>,
+        xp_ignored_channels -> Array<Int8>,
+        xp_ignored_roles -> Array<Int8>,
+        xp_allowed_channels -> Array<Int8>,
+        xp_whitelist_only -> Bool,
+        xp_multiplier_stack -> Varchar,
+        voice_xp_enabled -> Bool,
+        voice_xp_per_minute -> Int4,
+        voice_exclude_muted -> Bool,
+        voice_require_others -> Bool,
+        voice_exclude_afk -> Bool,
+        level_curve -> Varchar,
+        decay_enabled -> Bool,
+        decay_grace_days -> Int4,
+        decay_mode -> Varchar,
+        decay_amount -> Int4,
+        decay_min_level -> Int4,
+        decay_last_run -> Nullable<Date>,
+        departed_mode -> Varchar,
+        left_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    level_rewards (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        role_id -> Int8,
+        level -> Int4,
+    }
+}
+
+table! {
+    season_standings (season_id, position) {
+        season_id -> Int4,
+        position -> Int4,
+        user_id -> Int8,
+        xp -> Int4,
+    }
+}
+
+table! {
+    seasons (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        number -> Int4,
+        started_at -> Nullable<Timestamptz>,
+        ended_at -> Timestamptz,
+    }
+}
+
+table! {
+    user_settings (user_id) {
+        user_id -> Int8,
+        hide_guilds -> Bool,
     }
 }
 
@@ -13,7 +73,52 @@ table! {
         guild_id -> Int8,
         xp -> Int4,
         blocked -> Bool,
+        last_xp_at -> Timestamptz,
+        left_at -> Nullable<Timestamptz>,
     }
 }
 
-allow_tables_to_appear_in_same_query!(guilds, users,);
+table! {
+    xp_daily (guild_id, day, user_id) {
+        guild_id -> Int8,
+        day -> Date,
+        user_id -> Int8,
+        xp -> Int8,
+    }
+}
+
+table! {
+    xp_events (id) {
+        id -> Int8,
+        guild_id -> Int8,
+        user_id -> Int8,
+        xp -> Int4,
+        created_at -> Timestamptz,
+        source -> Varchar,
+        actor_id -> Nullable<Int8>,
+    }
+}
+
+table! {
+    xp_multipliers (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        target_id -> Int8,
+        kind -> Varchar,
+        multiplier -> Float4,
+    }
+}
+
+joinable!(season_standings -> seasons (season_id));
+
+allow_tables_to_appear_in_same_query!(
+    guilds,
+    level_rewards,
+    season_standings,
+    seasons,
+    user_settings,
+    users,
+    xp_daily,
+    xp_events,
+    xp_multipliers,
+);
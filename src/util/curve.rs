@@ -0,0 +1,392 @@
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// The highest level any curve can reach
+pub const MAX_LEVEL: i32 = 1000;
+/// The most levels a custom threshold table can have
+pub const TABLE_MAX_LEN: usize = 200;
+
+/// How many parts each XP is split into when working out an exponential
+/// curve's costs. Costs stop at `i64::MAX`, so growing one by up to 200%
+/// still fits in an `i128`
+const EXPONENTIAL_SCALE: i128 = 1_000_000_000_000_000;
+
+const CURVE_USAGE: &str = "Expected `linear <xp per level>`, `quadratic`, \
+                           `exponential <first level xp> <growth %>` or \
+                           `table <xp for level 1> <xp for level 2> ...`";
+
+/// How much XP each level costs. All of the math is done on integers, so a
+/// member is always at exactly one level for a given amount of XP
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LevelCurve {
+    /// Every level costs the same amount of XP
+    Linear { per_level: i64 },
+    /// MEE6's curve: going from level `n` to `n + 1` costs `5n² + 50n + 100`
+    #[default]
+    Quadratic,
+    /// The first level costs `base`, and each level after costs `growth`
+    /// percent more than the one before. Each level's cost is rounded down
+    /// from the exact amount
+    Exponential { base: i64, growth: i64 },
+    /// The total XP needed for each level, starting at level 1
+    Table(Vec<i64>),
+}
+
+impl LevelCurve {
+    /// The total XP needed to reach `level`, or `None` if the curve never
+    /// gets there
+    pub fn xp_for_level(&self, level: i32) -> Option<i64> {
+        if level <= 0 {
+            return Some(0);
+        }
+
+        if level > MAX_LEVEL {
+            return None;
+        }
+
+        let l = level as i64;
+
+        match self {
+            Self::Linear { per_level } => per_level.checked_mul(l),
+            // the sum of 5n² + 50n + 100 for n in 0..l
+            Self::Quadratic => Some(
+                5 * (l - 1) * l * (2 * l - 1) / 6 + 25 * l * (l - 1) + 100 * l,
+            ),
+            Self::Exponential { base, growth } => {
+                // the cost is carried in fixed point and only rounded down
+                // when it's added up, so small bases still grow
+                let mut cost = *base as i128 * EXPONENTIAL_SCALE;
+                let mut total = 0i64;
+
+                for n in 0..level {
+                    if n > 0 {
+                        cost = cost * (100 + *growth as i128) / 100;
+                    }
+
+                    let whole = i64::try_from(cost / EXPONENTIAL_SCALE).ok()?;
+                    total = total.checked_add(whole)?;
+                }
+
+                Some(total)
+            },
+            Self::Table(thresholds) => {
+                thresholds.get(level as usize - 1).copied()
+            },
+        }
+    }
+
+    /// The level a member with `xp` XP is at
+    pub fn level_for_xp(&self, xp: i32) -> i32 {
+        let xp = xp as i64;
+
+        // find the highest level whose threshold is at most `xp`
+        let (mut lo, mut hi) = (0, MAX_LEVEL);
+
+        while lo < hi {
+            let mid = lo + (hi - lo + 1) / 2;
+
+            match self.xp_for_level(mid) {
+                Some(threshold) if threshold <= xp => lo = mid,
+                _ => hi = mid - 1,
+            }
+        }
+
+        lo
+    }
+
+    /// The highest level a member can reach, which is lower than `MAX_LEVEL`
+    /// when a table is shorter or the XP needed doesn't fit in a member's XP
+    pub fn max_level(&self) -> i32 {
+        self.level_for_xp(i32::MAX)
+    }
+
+    /// Where a member with `xp` XP is on the curve
+    pub fn progress(&self, xp: i32) -> LevelProgress {
+        let level = self.level_for_xp(xp);
+        let threshold = self.xp_for_level(level).unwrap_or(0);
+        let next_threshold = self.xp_for_level(level + 1);
+        let xp = xp as i64;
+
+        let percent = match next_threshold {
+            Some(next) => ((xp - threshold) * 100 / (next - threshold)) as u8,
+            None => 100,
+        };
+
+        LevelProgress {
+            level,
+            xp,
+            threshold,
+            next_threshold,
+            xp_into_level: xp - threshold,
+            xp_to_next: next_threshold.map(|next| next - xp),
+            percent,
+        }
+    }
+
+    /// Check that every level costs more XP in total than the one before it
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Linear { per_level } if *per_level < 1 => {
+                Err("Each level has to cost at least 1 XP".to_string())
+            },
+            Self::Exponential { base, .. } if *base < 1 => {
+                Err("The first level has to cost at least 1 XP".to_string())
+            },
+            Self::Exponential { growth, .. } if !(1..=100).contains(growth) => {
+                Err("The growth must be between 1 and 100%".to_string())
+            },
+            Self::Table(thresholds) if thresholds.len() > TABLE_MAX_LEN => {
+                Err(format!(
+                    "A table can't have more than {} levels",
+                    TABLE_MAX_LEN
+                ))
+            },
+            Self::Table(thresholds)
+                if thresholds.first().is_none_or(|t| *t < 1)
+                    || thresholds.windows(2).any(|w| w[0] >= w[1]) =>
+            {
+                Err("Each level in the table has to need more XP than the one \
+                     before it"
+                    .to_string())
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A member's level, and how far they are through it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelProgress {
+    pub level: i32,
+    /// The member's total XP
+    pub xp: i64,
+    /// The total XP needed to reach `level`
+    pub threshold: i64,
+    /// The total XP needed to reach the next level, or `None` at the top of
+    /// the curve
+    pub next_threshold: Option<i64>,
+    /// The XP earned since reaching `level`
+    pub xp_into_level: i64,
+    /// The XP still needed to reach the next level
+    pub xp_to_next: Option<i64>,
+    /// How far through the level the member is, rounded down so it only
+    /// reads 100 at the top of the curve
+    pub percent: u8,
+}
+
+impl LevelProgress {
+    /// The XP the current level takes from start to finish
+    pub fn level_size(&self) -> Option<i64> {
+        self.next_threshold.map(|next| next - self.threshold)
+    }
+}
+
+impl fmt::Display for LevelCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Linear { per_level } => write!(f, "linear {}", per_level),
+            Self::Quadratic => f.write_str("quadratic"),
+            Self::Exponential { base, growth } => {
+                write!(f, "exponential {} {}", base, growth)
+            },
+            Self::Table(thresholds) => {
+                f.write_str("table")?;
+
+                for t in thresholds {
+                    write!(f, " {}", t)?;
+                }
+
+                Ok(())
+            },
+        }
+    }
+}
+
+impl FromStr for LevelCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|p| !p.is_empty());
+
+        let kind = parts.next().unwrap_or_default().to_lowercase();
+        let nums = parts
+            .map(|p| p.parse::<i64>())
+            .collect::<Result<Vec<i64>, _>>()
+            .map_err(|_| CURVE_USAGE.to_string())?;
+
+        let curve = match (kind.as_str(), nums.as_slice()) {
+            ("linear", [per_level]) => Self::Linear {
+                per_level: *per_level,
+            },
+            ("quadratic" | "mee6", []) => Self::Quadratic,
+            ("exponential", [base, growth]) => Self::Exponential {
+                base: *base,
+                growth: *growth,
+            },
+            ("table", thresholds) if !thresholds.is_empty() => {
+                Self::Table(thresholds.to_vec())
+            },
+            _ => return Err(CURVE_USAGE.to_string()),
+        };
+
+        curve.validate()?;
+
+        Ok(curve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The total XP needed for levels 1 to 100, from MEE6's level table
+    const MEE6_THRESHOLDS: [i64; 100] = [
+        100, 255, 475, 770, 1150, 1625, 2205, 2900, 3720, 4675, 5775, 7030,
+        8450, 10045, 11825, 13800, 15980, 18375, 20995, 23850, 26950, 30305,
+        33925, 37820, 42000, 46475, 51255, 56350, 61770, 67525, 73625, 80080,
+        86900, 94095, 101675, 109650, 118030, 126825, 136045, 145700, 155800,
+        166355, 177375, 188870, 200850, 213325, 226305, 239800, 253820, 268375,
+        283475, 299130, 315350, 332145, 349525, 367500, 386080, 405275, 425095,
+        445550, 466650, 488405, 510825, 533920, 557700, 582175, 607355, 633250,
+        659870, 687225, 715325, 744180, 773800, 804195, 835375, 867350, 900130,
+        933725, 968145, 1003400, 1039500, 1076455, 1114275, 1152970, 1192550,
+        1233025, 1274405, 1316700, 1359920, 1404075, 1449175, 1495230, 1542250,
+        1590245, 1639225, 1689200, 1740180, 1792175, 1845195, 1899250,
+    ];
+
+    #[test]
+    fn quadratic_matches_mee6_thresholds() {
+        let curve = LevelCurve::Quadratic;
+
+        assert_eq!(curve.xp_for_level(0), Some(0));
+
+        for (i, threshold) in MEE6_THRESHOLDS.iter().enumerate() {
+            assert_eq!(curve.xp_for_level(i as i32 + 1), Some(*threshold));
+        }
+    }
+
+    #[test]
+    fn quadratic_levels_change_exactly_at_thresholds() {
+        let curve = LevelCurve::Quadratic;
+
+        assert_eq!(curve.level_for_xp(0), 0);
+
+        for (i, threshold) in MEE6_THRESHOLDS.iter().enumerate() {
+            let level = i as i32 + 1;
+            let xp = *threshold as i32;
+
+            assert_eq!(curve.level_for_xp(xp - 1), level - 1);
+            assert_eq!(curve.level_for_xp(xp), level);
+            assert_eq!(curve.level_for_xp(xp + 1), level);
+        }
+    }
+
+    #[test]
+    fn quadratic_progress_at_thresholds() {
+        let curve = LevelCurve::Quadratic;
+
+        for (i, pair) in MEE6_THRESHOLDS.windows(2).enumerate() {
+            let level = i as i32 + 1;
+            let (threshold, next) = (pair[0], pair[1]);
+            let xp = threshold as i32;
+
+            // one short of the level: 99% through the previous one
+            let before = curve.progress(xp - 1);
+            assert_eq!(before.level, level - 1);
+            assert_eq!(before.xp_to_next, Some(1));
+            assert_eq!(before.percent, 99);
+
+            let at = curve.progress(xp);
+            assert_eq!(at.level, level);
+            assert_eq!(at.threshold, threshold);
+            assert_eq!(at.next_threshold, Some(next));
+            assert_eq!(at.xp_into_level, 0);
+            assert_eq!(at.xp_to_next, Some(next - threshold));
+            assert_eq!(at.percent, 0);
+
+            let after = curve.progress(xp + 1);
+            assert_eq!(after.level, level);
+            assert_eq!(after.xp_into_level, 1);
+            assert_eq!(after.xp_to_next, Some(next - threshold - 1));
+            assert_eq!(after.percent, 0);
+        }
+    }
+
+    #[test]
+    fn quadratic_progress_mid_level() {
+        // level 1 runs from 100 to 255 XP
+        let progress = LevelCurve::Quadratic.progress(177);
+
+        assert_eq!(progress.level, 1);
+        assert_eq!(progress.xp_into_level, 77);
+        assert_eq!(progress.xp_to_next, Some(78));
+        assert_eq!(progress.level_size(), Some(155));
+        assert_eq!(progress.percent, 49);
+    }
+
+    #[test]
+    fn exponential_growth_compounds() {
+        let curve = LevelCurve::Exponential {
+            base: 100,
+            growth: 10,
+        };
+
+        // 100, 110, 121, 133.1, 146.41
+        assert_eq!(curve.xp_for_level(1), Some(100));
+        assert_eq!(curve.xp_for_level(2), Some(210));
+        assert_eq!(curve.xp_for_level(3), Some(331));
+        assert_eq!(curve.xp_for_level(4), Some(464));
+        assert_eq!(curve.xp_for_level(5), Some(610));
+    }
+
+    #[test]
+    fn exponential_growth_on_small_bases() {
+        let curve = LevelCurve::Exponential { base: 1, growth: 1 };
+
+        // 1.01^70 is just over 2, so level 71 is the first to cost 2 XP
+        assert_eq!(curve.xp_for_level(70), Some(70));
+        assert_eq!(curve.xp_for_level(71), Some(72));
+        assert!(curve.xp_for_level(MAX_LEVEL).unwrap() > 1000);
+    }
+
+    #[test]
+    fn exponential_stops_when_xp_overflows() {
+        let curve = LevelCurve::Exponential {
+            base: 1_000_000,
+            growth: 100,
+        };
+
+        assert_eq!(curve.xp_for_level(MAX_LEVEL), None);
+        assert_eq!(curve.level_for_xp(i32::MAX), 11);
+        assert_eq!(curve.max_level(), 11);
+    }
+
+    #[test]
+    fn max_level_of_each_curve() {
+        assert_eq!(LevelCurve::Quadratic.max_level(), MAX_LEVEL);
+        assert_eq!(LevelCurve::Table(vec![10, 20, 30]).max_level(), 3);
+        assert_eq!(
+            LevelCurve::Linear {
+                per_level: i32::MAX as i64
+            }
+            .max_level(),
+            1
+        );
+    }
+
+    #[test]
+    fn progress_at_top_of_curve() {
+        let curve = LevelCurve::Quadratic;
+        let top = curve.xp_for_level(MAX_LEVEL).unwrap();
+
+        assert_eq!(curve.xp_for_level(MAX_LEVEL + 1), None);
+        assert_eq!(curve.level_for_xp(top as i32 - 1), MAX_LEVEL - 1);
+
+        let progress = curve.progress(i32::MAX);
+        assert_eq!(progress.level, MAX_LEVEL);
+        assert_eq!(progress.next_threshold, None);
+        assert_eq!(progress.xp_to_next, None);
+        assert_eq!(progress.percent, 100);
+    }
+}
@@ -0,0 +1,19 @@
+/// Write a number with commas between the thousands, like 3,104
+pub fn thousands(n: i64) -> String {
+    let digits = n.abs().to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+
+    if n < 0 {
+        out.push('-');
+    }
+
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+
+        out.push(c);
+    }
+
+    out
+}
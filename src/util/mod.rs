@@ -1 +1,4 @@
-pub mod xp;
+pub mod curve;
+pub mod format;
+pub mod parse;
+pub mod template;
@@ -0,0 +1,50 @@
+use serenity::{model::prelude::*, prelude::*};
+
+/// Find a role in the guild by mention, ID or name
+pub fn parse_role(guild: &Guild, arg: &str) -> Option<RoleId> {
+    let arg = arg.trim();
+
+    match arg.parse::<RoleId>() {
+        Ok(id) if guild.roles.contains_key(&id) => Some(id),
+        _ => guild.role_by_name(arg).map(|r| r.id),
+    }
+}
+
+/// Find a channel or category in the guild by mention, ID or name
+pub async fn parse_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    arg: &str,
+) -> Option<ChannelId> {
+    let arg = arg.trim();
+    let channels = ctx.cache.guild_channels(guild_id).await?;
+
+    match arg.parse::<ChannelId>() {
+        Ok(id) if channels.contains_key(&id) => Some(id),
+        _ => channels
+            .values()
+            .find(|c| c.name.eq_ignore_ascii_case(arg.trim_start_matches('#')))
+            .map(|c| c.id),
+    }
+}
+
+/// Find a member of the guild by mention, ID or name
+pub async fn parse_member(
+    ctx: &Context,
+    guild_id: GuildId,
+    arg: &str,
+) -> Option<User> {
+    let arg = arg.trim();
+
+    if let Ok(id) = arg.parse::<UserId>() {
+        return match ctx.cache.member(guild_id, id).await {
+            Some(m) => Some(m.user),
+            None => guild_id.member(ctx, id).await.ok().map(|m| m.user),
+        };
+    }
+
+    ctx.cache
+        .guild_field(guild_id, |g| g.member_named(arg).map(|m| m.user.clone()))
+        .await
+        .flatten()
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use fluent_templates::{
+    fluent_bundle::{FluentArgs, FluentBundle, FluentResource, FluentValue},
+    loader::langid,
+    Loader,
+};
+use serenity::model::user::User;
+
+use crate::{args, util::curve::LevelCurve, LOCALES};
+
+/// The longest level-up template a guild can save
+pub const TEMPLATE_MAX_LEN: usize = 1000;
+
+/// The id the guild's template is stored under in its one-off bundle
+const TEMPLATE_ID: &str = "level-up-custom";
+
+/// The values that can be used in a level-up template
+pub struct LevelUpVars {
+    pub mention: String,
+    pub username: String,
+    pub level: i32,
+    pub xp: i32,
+    pub xp_to_next: i32,
+    pub server: String,
+}
+
+impl LevelUpVars {
+    pub fn new(
+        user: &User,
+        xp: i32,
+        curve: &LevelCurve,
+        server: String,
+    ) -> Self {
+        let progress = curve.progress(xp);
+
+        Self {
+            mention: format!("<@{}>", user.id),
+            username: user.name.clone(),
+            level: progress.level,
+            xp,
+            xp_to_next: progress.xp_to_next.unwrap_or(0) as i32,
+            server,
+        }
+    }
+
+    fn to_args(&self) -> HashMap<&'static str, FluentValue<'static>> {
+        args! {
+            "mention" => self.mention.clone(),
+            "username" => self.username.clone(),
+            "level" => self.level,
+            "xp" => self.xp,
+            "xp_to_next" => self.xp_to_next,
+            "server" => self.server.clone(),
+        }
+    }
+
+    /// Placeholder values used to check that a template renders
+    fn sample() -> Self {
+        Self {
+            mention: "<@0>".to_string(),
+            username: "user".to_string(),
+            level: 1,
+            xp: 100,
+            xp_to_next: 155,
+            server: "server".to_string(),
+        }
+    }
+}
+
+/// Check that a level-up template parses and only uses known variables
+pub fn validate_template(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Err("The message can't be empty".to_string());
+    }
+
+    if template.chars().count() > TEMPLATE_MAX_LEN {
+        return Err(format!(
+            "The message can't be longer than {} characters",
+            TEMPLATE_MAX_LEN
+        ));
+    }
+
+    let rendered = try_render(template, &LevelUpVars::sample())?;
+
+    if rendered.trim().is_empty() {
+        return Err("The message can't be empty".to_string());
+    }
+
+    Ok(())
+}
+
+/// Render a level-up announcement, falling back to the default message if the
+/// guild hasn't set a template or the saved one no longer renders
+pub fn render_level_up(template: Option<&str>, vars: &LevelUpVars) -> String {
+    template
+        .and_then(|t| try_render(t, vars).ok())
+        .unwrap_or_else(|| {
+            LOCALES.lookup_with_args(
+                &langid!("en"),
+                "level-up",
+                &vars.to_args(),
+            )
+        })
+}
+
+fn try_render(template: &str, vars: &LevelUpVars) -> Result<String, String> {
+    // continuation lines have to be indented to stay part of the message
+    let source = format!(
+        "{} = {}",
+        TEMPLATE_ID,
+        template.trim().replace('\n', "\n    ")
+    );
+
+    let resource = FluentResource::try_new(source)
+        .map_err(|_| "The message isn't a valid template".to_string())?;
+
+    let mut bundle = FluentBundle::new(vec![langid!("en")]);
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .map_err(|_| "The message isn't a valid template".to_string())?;
+
+    let pattern = bundle
+        .get_message(TEMPLATE_ID)
+        .and_then(|m| m.value)
+        .ok_or_else(|| "The message isn't a valid template".to_string())?;
+
+    let mut fluent_args = FluentArgs::new();
+    for (k, v) in vars.to_args() {
+        fluent_args.add(k, v);
+    }
+
+    let mut errors = vec![];
+    let rendered =
+        bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+
+    match errors.first() {
+        None => Ok(rendered.to_string()),
+        Some(e) => Err(format!("The message couldn't be rendered: {}", e)),
+    }
+}
@@ -0,0 +1,208 @@
+use std::time::{Duration, Instant};
+
+use serenity::{model::prelude::*, prelude::*};
+
+use crate::{
+    db::postgres::Database,
+    levelup::{apply_multipliers, grant_xp},
+    models::xp_event::XpSource,
+    VoiceSessions,
+    VOICE_XP_LIMIT,
+};
+
+/// How often time spent in voice is turned into XP
+const VOICE_TICK: Duration = Duration::from_secs(60);
+
+/// A member's voice state, and the bits of the guild needed to decide whether
+/// it earns XP, copied out of the cache
+struct VoiceMember {
+    user: User,
+    roles: Vec<RoleId>,
+    channel: ChannelId,
+    category: Option<ChannelId>,
+    muted: bool,
+    afk: bool,
+    /// Other members in the channel, not counting bots
+    others: usize,
+}
+
+/// Start timing a member when they join a voice channel, and stop when they
+/// leave. Moving between channels keeps the same session
+pub async fn track_voice_state(
+    ctx: &Context,
+    guild_id: GuildId,
+    state: &VoiceState,
+) {
+    if state.member.as_ref().is_some_and(|m| m.user.bot) {
+        return;
+    }
+
+    let data = ctx.data.read().await;
+    let mut sessions = data
+        .get::<VoiceSessions>()
+        .expect("Expected `VoiceSessions` in TypeMap")
+        .lock()
+        .await;
+
+    let key = (guild_id, state.user_id);
+
+    if state.channel_id.is_some() {
+        sessions.entry(key).or_insert_with(Instant::now);
+    } else {
+        sessions.remove(&key);
+    }
+}
+
+/// Start timing everyone already in voice when a guild becomes available, so
+/// members who were in a channel before the bot started are credited too
+pub async fn track_guild_voice_states(ctx: &Context, guild: &Guild) {
+    let data = ctx.data.read().await;
+    let mut sessions = data
+        .get::<VoiceSessions>()
+        .expect("Expected `VoiceSessions` in TypeMap")
+        .lock()
+        .await;
+
+    let now = Instant::now();
+
+    for state in guild.voice_states.values() {
+        let is_bot = guild
+            .members
+            .get(&state.user_id)
+            .is_some_and(|m| m.user.bot);
+
+        if state.channel_id.is_some() && !is_bot {
+            sessions.entry((guild.id, state.user_id)).or_insert(now);
+        }
+    }
+}
+
+/// Spawn the task that hands out voice XP every minute
+pub fn start_voice_ticker(ctx: Context) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(VOICE_TICK);
+
+        loop {
+            interval.tick().await;
+            credit_voice_time(&ctx).await;
+        }
+    });
+}
+
+/// Credit every member for the whole minutes they've spent in voice since
+/// they were last credited. Partial minutes carry over to the next tick
+async fn credit_voice_time(ctx: &Context) {
+    let due = {
+        let data = ctx.data.read().await;
+        let mut sessions = data
+            .get::<VoiceSessions>()
+            .expect("Expected `VoiceSessions` in TypeMap")
+            .lock()
+            .await;
+
+        let now = Instant::now();
+        let mut due = Vec::new();
+
+        for (key, last_credit) in sessions.iter_mut() {
+            let minutes = now.duration_since(*last_credit).as_secs() / 60;
+
+            if minutes > 0 {
+                *last_credit += Duration::from_secs(minutes * 60);
+                due.push((*key, minutes as i32));
+            }
+        }
+
+        due
+    };
+
+    for ((guild_id, user_id), minutes) in due {
+        credit_member(ctx, guild_id, user_id, minutes).await;
+    }
+}
+
+async fn credit_member(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    minutes: i32,
+) {
+    let member = ctx
+        .cache
+        .guild_field(guild_id, |g| voice_member(g, user_id))
+        .await
+        .flatten();
+
+    let member = match member {
+        Some(m) => m,
+        None => return,
+    };
+
+    let (settings, multipliers) = {
+        let data = ctx.data.read().await;
+        let db = data
+            .get::<Database>()
+            .expect("Expected `Database` in TypeMap")
+            .lock()
+            .await;
+
+        let settings = match db.get_guild(guild_id) {
+            Ok(g) => g,
+            Err(_) => return,
+        };
+
+        (
+            settings,
+            db.get_xp_multipliers(guild_id).unwrap_or_default(),
+        )
+    };
+
+    if !settings.voice_xp_enabled
+        || (settings.voice_exclude_muted && member.muted)
+        || (settings.voice_require_others && member.others == 0)
+        || (settings.voice_exclude_afk && member.afk)
+        || !settings.earns_xp(member.channel, member.category, &member.roles)
+    {
+        return;
+    }
+
+    let per_minute = apply_multipliers(
+        &settings,
+        &multipliers,
+        settings.voice_xp_per_minute,
+        VOICE_XP_LIMIT,
+        member.channel,
+        member.category,
+        &member.roles,
+    );
+    let xp = per_minute.saturating_mul(minutes);
+
+    if xp > 0 {
+        grant_xp(ctx, &settings, &member.user, xp, XpSource::Voice, None).await;
+    }
+}
+
+fn voice_member(guild: &Guild, user_id: UserId) -> Option<VoiceMember> {
+    let state = guild.voice_states.get(&user_id)?;
+    let channel = state.channel_id?;
+    let member = guild.members.get(&user_id)?;
+
+    let is_bot =
+        |id: &UserId| guild.members.get(id).is_some_and(|m| m.user.bot);
+
+    let others = guild
+        .voice_states
+        .values()
+        .filter(|s| s.channel_id == Some(channel))
+        .filter(|s| s.user_id != user_id && !is_bot(&s.user_id))
+        .count();
+
+    Some(VoiceMember {
+        user: member.user.clone(),
+        roles: member.roles.clone(),
+        channel,
+        category: guild.channels.get(&channel).and_then(|c| c.category_id),
+        muted: state.mute || state.deaf || state.self_mute || state.self_deaf,
+        afk: guild.afk_channel_id == Some(channel),
+        others,
+    })
+}